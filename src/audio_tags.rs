@@ -0,0 +1,975 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum AudioTag {
+    Title,
+    Artist,
+    AlbumArtist,
+    Album,
+    Track,
+    Disc,
+    Year,
+    Genre,
+}
+
+const AUDIO_TAGS: [AudioTag; 8] = [
+    AudioTag::Title,
+    AudioTag::Artist,
+    AudioTag::AlbumArtist,
+    AudioTag::Album,
+    AudioTag::Track,
+    AudioTag::Disc,
+    AudioTag::Year,
+    AudioTag::Genre,
+];
+
+impl AudioTag {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        AUDIO_TAGS.iter().copied().find(|tag| tag.name() == name)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            AudioTag::Title => "title",
+            AudioTag::Artist => "artist",
+            AudioTag::AlbumArtist => "albumartist",
+            AudioTag::Album => "album",
+            AudioTag::Track => "track",
+            AudioTag::Disc => "disc",
+            AudioTag::Year => "year",
+            AudioTag::Genre => "genre",
+        }
+    }
+
+    fn from_vorbis_key(key: &str) -> Option<Self> {
+        match key.to_uppercase().as_str() {
+            "TITLE" => Some(AudioTag::Title),
+            "ARTIST" => Some(AudioTag::Artist),
+            "ALBUMARTIST" | "ALBUM ARTIST" => Some(AudioTag::AlbumArtist),
+            "ALBUM" => Some(AudioTag::Album),
+            "TRACKNUMBER" => Some(AudioTag::Track),
+            "DISCNUMBER" => Some(AudioTag::Disc),
+            "DATE" | "YEAR" => Some(AudioTag::Year),
+            "GENRE" => Some(AudioTag::Genre),
+            _ => None,
+        }
+    }
+
+    fn from_id3_frame(id: &[u8]) -> Option<Self> {
+        // ID3v2.2 uses three characters, later versions four
+        match id {
+            b"TIT2" | b"TT2" => Some(AudioTag::Title),
+            b"TPE1" | b"TP1" => Some(AudioTag::Artist),
+            b"TPE2" | b"TP2" => Some(AudioTag::AlbumArtist),
+            b"TALB" | b"TAL" => Some(AudioTag::Album),
+            b"TRCK" | b"TRK" => Some(AudioTag::Track),
+            b"TPOS" | b"TPA" => Some(AudioTag::Disc),
+            b"TYER" | b"TDRC" | b"TYE" => Some(AudioTag::Year),
+            b"TCON" | b"TCO" => Some(AudioTag::Genre),
+            _ => None,
+        }
+    }
+
+    fn from_mp4_atom(atom: &[u8]) -> Option<Self> {
+        match atom {
+            b"\xa9nam" => Some(AudioTag::Title),
+            b"\xa9ART" => Some(AudioTag::Artist),
+            b"aART" => Some(AudioTag::AlbumArtist),
+            b"\xa9alb" => Some(AudioTag::Album),
+            b"trkn" => Some(AudioTag::Track),
+            b"disk" => Some(AudioTag::Disc),
+            b"\xa9day" => Some(AudioTag::Year),
+            b"\xa9gen" => Some(AudioTag::Genre),
+            _ => None,
+        }
+    }
+}
+
+type AudioTags = HashMap<AudioTag, String>;
+
+/// The genres that ID3 tags refer to by their index.
+const ID3V1_GENRES: [&str; 80] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "Alternative Rock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native US",
+    "Cabaret",
+    "New Wave",
+    "Psychadelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+];
+
+/// Reads the tags of an MP3 (ID3v1 and ID3v2), FLAC, Ogg (Vorbis and Opus) or MP4 file.
+/// The values are sanitized so that they can be used as a single path component.
+/// Files without (valid) tags yield no tags.
+pub(crate) fn read_audio_tags(path: &Path) -> AudioTags {
+    File::open(path)
+        .and_then(|file| read_audio_tags_from(BufReader::new(file)))
+        .unwrap_or_default()
+}
+
+fn read_audio_tags_from(mut reader: impl Read + Seek) -> io::Result<AudioTags> {
+    let mut signature = [0; 8];
+    let signature_length = read_up_to(&mut reader, &mut signature)?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let raw_tags = match &signature[..signature_length] {
+        [b'f', b'L', b'a', b'C', ..] => read_flac_tags(reader)?,
+        [b'O', b'g', b'g', b'S', ..] => read_ogg_tags(reader)?,
+        [_, _, _, _, b'f', b't', b'y', b'p'] => read_mp4_tags(reader)?,
+        _ => read_id3_tags(reader)?,
+    };
+
+    Ok(raw_tags
+        .into_iter()
+        .filter_map(|(tag, value)| {
+            let value = normalize(tag, &value)?;
+            Some((tag, sanitize(&value))).filter(|(_, value)| !value.is_empty())
+        })
+        .collect())
+}
+
+/// Values of the ID3v2 tag at the start take precedence over those of the ID3v1 tag at the end.
+fn read_id3_tags(mut reader: impl Read + Seek) -> io::Result<AudioTags> {
+    let mut tags = read_id3v2_tags(&mut reader)?;
+
+    for (tag, value) in read_id3v1_tags(&mut reader)? {
+        tags.entry(tag).or_insert(value);
+    }
+
+    Ok(tags)
+}
+
+fn read_id3v2_tags(reader: &mut (impl Read + Seek)) -> io::Result<AudioTags> {
+    const UNSYNCHRONISATION: u8 = 0x80;
+    const EXTENDED_HEADER: u8 = 0x40;
+
+    let mut header = [0; 10];
+    reader.seek(SeekFrom::Start(0))?;
+
+    let (version, flags, size) = match read_up_to(reader, &mut header)? {
+        10 if &header[..3] == b"ID3" => (header[3], header[5], syncsafe(&header[6..])),
+        _ => return Ok(HashMap::new()),
+    };
+
+    // The sizes of ID3v2.2 tags with this flag refer to compressed data
+    let size = match size {
+        Some(size) if (2..=4).contains(&version) && !(version == 2 && flags & 0x40 != 0) => size,
+        _ => return Ok(HashMap::new()),
+    };
+
+    let mut data = Vec::new();
+    reader.take(u64::from(size)).read_to_end(&mut data)?;
+
+    if flags & UNSYNCHRONISATION != 0 && version < 4 {
+        data = resynchronize(&data);
+    }
+
+    let frames = if flags & EXTENDED_HEADER != 0 && version > 2 {
+        let extended_header_size = match version {
+            3 => data.get(..4).map(|size| be_u32(size) as usize + 4),
+            _ => data.get(..4).and_then(syncsafe).map(|size| size as usize),
+        };
+
+        match extended_header_size.and_then(|size| data.get(size..)) {
+            Some(frames) => frames,
+            None => return Ok(HashMap::new()),
+        }
+    } else {
+        &data
+    };
+
+    Ok(
+        id3v2_frames(frames, version, flags & UNSYNCHRONISATION != 0)
+            .filter_map(|(id, contents)| {
+                let tag = AudioTag::from_id3_frame(id)?;
+                let value = decode_id3_text(&contents)?;
+
+                match tag {
+                    AudioTag::Genre => Some((tag, parse_id3_genre(&value)?)),
+                    _ => Some((tag, value)),
+                }
+            })
+            .fold(HashMap::new(), |mut tags, (tag, value)| {
+                tags.entry(tag).or_insert(value);
+                tags
+            }),
+    )
+}
+
+/// Returns the identifier and the contents of each readable frame.
+fn id3v2_frames(
+    data: &[u8],
+    version: u8,
+    is_unsynchronised: bool,
+) -> impl Iterator<Item = (&[u8], Vec<u8>)> {
+    // The format flags of ID3v2.2, ID3v2.3 and ID3v2.4
+    const COMPRESSION_OR_ENCRYPTION: [u8; 3] = [0, 0xc0, 0x0c];
+    const GROUPING: [u8; 3] = [0, 0x20, 0x40];
+    const UNSYNCHRONISATION: u8 = 0x02;
+    const DATA_LENGTH_INDICATOR: u8 = 0x01;
+
+    let (id_length, header_length) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut remaining = data;
+
+    std::iter::from_fn(move || loop {
+        let header = remaining.get(..header_length)?;
+        // Padding follows the last frame
+        if header[0] == 0 {
+            return None;
+        }
+
+        let size = match version {
+            2 => be_u32(&[0, header[3], header[4], header[5]]),
+            3 => be_u32(&header[4..8]),
+            _ => syncsafe(&header[4..8])?,
+        } as usize;
+
+        let contents = remaining.get(header_length..header_length.checked_add(size)?)?;
+        remaining = &remaining[header_length + size..];
+
+        let format_flags = if version == 2 { 0 } else { header[9] };
+        let has_flag = |flags: [u8; 3]| format_flags & flags[version as usize - 2] != 0;
+        if has_flag(COMPRESSION_OR_ENCRYPTION) {
+            continue;
+        }
+
+        // The group identifier precedes the contents
+        let contents = if has_flag(GROUPING) {
+            contents.get(1..)?
+        } else {
+            contents
+        };
+
+        let contents = if version == 4 {
+            let contents = if format_flags & DATA_LENGTH_INDICATOR != 0 {
+                contents.get(4..)?
+            } else {
+                contents
+            };
+
+            if is_unsynchronised || format_flags & UNSYNCHRONISATION != 0 {
+                resynchronize(contents)
+            } else {
+                contents.to_vec()
+            }
+        } else {
+            contents.to_vec()
+        };
+
+        return Some((&header[..id_length], contents));
+    })
+}
+
+/// Decodes a text frame, which starts with its encoding. Only the first of several values is used.
+fn decode_id3_text(contents: &[u8]) -> Option<String> {
+    let (&encoding, text) = contents.split_first()?;
+
+    let utf16 = |text: &[u8], decode: fn([u8; 2]) -> u16| {
+        let units = text.chunks_exact(2).map(|pair| decode([pair[0], pair[1]]));
+        char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect::<String>()
+    };
+
+    let text = match encoding {
+        0 => text.iter().copied().map(char::from).collect(),
+        1 => match text {
+            [0xff, 0xfe, rest @ ..] => utf16(rest, u16::from_le_bytes),
+            [0xfe, 0xff, rest @ ..] => utf16(rest, u16::from_be_bytes),
+            _ => utf16(text, u16::from_be_bytes),
+        },
+        2 => utf16(text, u16::from_be_bytes),
+        3 => String::from_utf8_lossy(text).into_owned(),
+        _ => return None,
+    };
+
+    text.split('\0').next().map(String::from)
+}
+
+/// Genres may refer to the ID3v1 genres, e.g. `(17)`, `17` or `(17)Rock`.
+fn parse_id3_genre(value: &str) -> Option<String> {
+    let genre = |index: &str| {
+        let index: usize = index.parse().ok()?;
+        ID3V1_GENRES.get(index).map(|genre| String::from(*genre))
+    };
+
+    match value
+        .strip_prefix('(')
+        .and_then(|value| value.split_once(')'))
+    {
+        Some((index, "")) => genre(index),
+        Some((_, refinement)) => Some(String::from(refinement)),
+        None if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) => genre(value),
+        None => Some(String::from(value)),
+    }
+}
+
+/// The fixed size tag in the last 128 bytes, with the track in the comment since ID3v1.1.
+fn read_id3v1_tags(reader: &mut (impl Read + Seek)) -> io::Result<AudioTags> {
+    let length = reader.seek(SeekFrom::End(0))?;
+    if length < 128 {
+        return Ok(HashMap::new());
+    }
+
+    let mut tag = [0; 128];
+    reader.seek(SeekFrom::Start(length - 128))?;
+    reader.read_exact(&mut tag)?;
+
+    if &tag[..3] != b"TAG" {
+        return Ok(HashMap::new());
+    }
+
+    let text = |field: &[u8]| {
+        let text: String = field
+            .iter()
+            .take_while(|&&byte| byte != 0)
+            .copied()
+            .map(char::from)
+            .collect();
+        Some(text).filter(|text| !text.trim().is_empty())
+    };
+
+    let values = vec![
+        (AudioTag::Title, text(&tag[3..33])),
+        (AudioTag::Artist, text(&tag[33..63])),
+        (AudioTag::Album, text(&tag[63..93])),
+        (AudioTag::Year, text(&tag[93..97])),
+        (
+            AudioTag::Track,
+            Some(tag[126].to_string()).filter(|_| tag[125] == 0 && tag[126] != 0),
+        ),
+        (
+            AudioTag::Genre,
+            ID3V1_GENRES
+                .get(tag[127] as usize)
+                .map(|genre| String::from(*genre)),
+        ),
+    ];
+
+    Ok(values
+        .into_iter()
+        .filter_map(|(tag, value)| Some((tag, value?)))
+        .collect())
+}
+
+/// Sizes in ID3v2 headers use seven bits per byte.
+fn syncsafe(bytes: &[u8]) -> Option<u32> {
+    bytes.iter().try_fold(0, |size, &byte| {
+        Some((size << 7) | u32::from(byte)).filter(|_| byte < 0x80)
+    })
+}
+
+/// Removes the zero bytes that were inserted after each `0xff` byte.
+fn resynchronize(data: &[u8]) -> Vec<u8> {
+    let mut resynchronized = Vec::with_capacity(data.len());
+    let mut previous = 0;
+
+    for &byte in data {
+        if !(previous == 0xff && byte == 0) {
+            resynchronized.push(byte);
+        }
+        previous = byte;
+    }
+
+    resynchronized
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes.try_into().unwrap())
+}
+
+fn read_flac_tags(mut reader: impl Read + Seek) -> io::Result<AudioTags> {
+    const VORBIS_COMMENT: u8 = 4;
+
+    reader.seek(SeekFrom::Start(4))?;
+
+    loop {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+
+        let is_last = header[0] & 0x80 != 0;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+
+        if header[0] & 0x7f == VORBIS_COMMENT {
+            let mut block = vec![0; length as usize];
+            reader.read_exact(&mut block)?;
+            return Ok(parse_vorbis_comments(&block));
+        }
+
+        if is_last {
+            return Ok(HashMap::new());
+        }
+
+        reader.seek(SeekFrom::Current(i64::from(length)))?;
+    }
+}
+
+/// The comment header is the second packet of the first logical stream.
+fn read_ogg_tags(mut reader: impl Read) -> io::Result<AudioTags> {
+    const MAX_HEADER_SIZE: usize = 16 * 1024 * 1024;
+
+    let mut packets: Vec<Vec<u8>> = vec![Vec::new()];
+
+    while packets.len() < 3 && packets.iter().map(Vec::len).sum::<usize>() < MAX_HEADER_SIZE {
+        let mut header = [0; 27];
+        reader.read_exact(&mut header)?;
+
+        if &header[..4] != b"OggS" {
+            break;
+        }
+
+        let mut segment_table = vec![0; header[26] as usize];
+        reader.read_exact(&mut segment_table)?;
+
+        for segment_length in segment_table {
+            let mut segment = vec![0; segment_length as usize];
+            reader.read_exact(&mut segment)?;
+            packets.last_mut().unwrap().extend(segment);
+
+            if segment_length < 255 {
+                packets.push(Vec::new());
+            }
+        }
+    }
+
+    let comment_header = match packets.get(1) {
+        Some(packet) if packets.len() > 2 => packet,
+        _ => return Ok(HashMap::new()),
+    };
+
+    let comments = if comment_header.starts_with(b"\x03vorbis") {
+        &comment_header[7..]
+    } else if comment_header.starts_with(b"OpusTags") {
+        &comment_header[8..]
+    } else {
+        return Ok(HashMap::new());
+    };
+
+    Ok(parse_vorbis_comments(comments))
+}
+
+fn parse_vorbis_comments(data: &[u8]) -> AudioTags {
+    let mut tags = HashMap::new();
+    let mut remaining = data;
+
+    let mut take = |length: usize| {
+        let (taken, rest) = (remaining.get(..length)?, remaining.get(length..)?);
+        remaining = rest;
+        Some(taken)
+    };
+
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap()) as usize;
+
+    let _vendor = take(4).map(read_u32).and_then(&mut take);
+    let count = take(4).map(read_u32).unwrap_or(0);
+
+    for _ in 0..count {
+        let comment = match take(4).map(read_u32).and_then(&mut take) {
+            Some(comment) => String::from_utf8_lossy(comment),
+            None => break,
+        };
+
+        let mut parts = comment.splitn(2, '=');
+        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+            if let Some(tag) = AudioTag::from_vorbis_key(key) {
+                tags.entry(tag).or_insert_with(|| String::from(value));
+            }
+        }
+    }
+
+    tags
+}
+
+/// The tags are stored in `moov.udta.meta.ilst`, where each item holds a `data` atom.
+fn read_mp4_tags(mut reader: impl Read + Seek) -> io::Result<AudioTags> {
+    let file_length = reader.seek(SeekFrom::End(0))?;
+
+    let moov = find_atom(&mut reader, (0, file_length), b"moov")?;
+    let udta = find_and_then(&mut reader, moov, b"udta")?;
+    let meta = find_and_then(&mut reader, udta, b"meta")?;
+    // The meta atom starts with a version and flags
+    let ilst = find_and_then(
+        &mut reader,
+        meta.map(|(start, end)| (start + 4, end)),
+        b"ilst",
+    )?;
+
+    const MAX_ITEMS_SIZE: u64 = 64 * 1024 * 1024;
+
+    let (start, end) = match ilst {
+        Some((start, end)) if end - start <= MAX_ITEMS_SIZE => (start, end),
+        _ => return Ok(HashMap::new()),
+    };
+
+    let mut items = vec![0; (end - start) as usize];
+    reader.seek(SeekFrom::Start(start))?;
+    reader.read_exact(&mut items)?;
+
+    Ok(child_atoms(&items)
+        .filter_map(|(name, item)| {
+            let tag = AudioTag::from_mp4_atom(name)?;
+            let (_, data) = child_atoms(item).find(|(name, _)| name == b"data")?;
+            // The payload follows the type and the locale
+            let payload = data.get(8..)?;
+
+            let value = match tag {
+                AudioTag::Track | AudioTag::Disc => {
+                    u16::from_be_bytes([*payload.get(2)?, *payload.get(3)?]).to_string()
+                }
+                _ => String::from_utf8_lossy(payload).into_owned(),
+            };
+
+            Some((tag, value))
+        })
+        .collect())
+}
+
+fn find_and_then(
+    reader: &mut (impl Read + Seek),
+    range: Option<(u64, u64)>,
+    name: &[u8; 4],
+) -> io::Result<Option<(u64, u64)>> {
+    match range {
+        Some(range) => find_atom(reader, range, name),
+        None => Ok(None),
+    }
+}
+
+/// Returns the range of the contents of the first atom with the given name.
+fn find_atom(
+    reader: &mut (impl Read + Seek),
+    (start, end): (u64, u64),
+    name: &[u8; 4],
+) -> io::Result<Option<(u64, u64)>> {
+    let mut position = start;
+
+    while end.saturating_sub(position) >= 8 {
+        let mut header = [0; 8];
+        reader.seek(SeekFrom::Start(position))?;
+        reader.read_exact(&mut header)?;
+
+        let (header_length, size) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            0 => (8, end - position),
+            1 => {
+                let mut extended_size = [0; 8];
+                reader.read_exact(&mut extended_size)?;
+                (16, u64::from_be_bytes(extended_size))
+            }
+            size => (8, u64::from(size)),
+        };
+
+        // Sizes are read from the file, so they may point anywhere
+        let (contents_start, atom_end) = match (
+            position.checked_add(header_length),
+            position.checked_add(size),
+        ) {
+            (Some(contents_start), Some(atom_end))
+                if size >= header_length && contents_start <= end =>
+            {
+                (contents_start, atom_end)
+            }
+            _ => break,
+        };
+
+        if &header[4..] == name {
+            return Ok(Some((contents_start, atom_end.min(end))));
+        }
+
+        position = atom_end;
+    }
+
+    Ok(None)
+}
+
+fn child_atoms(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut remaining = data;
+
+    std::iter::from_fn(move || {
+        let size = u32::from_be_bytes(remaining.get(..4)?.try_into().unwrap()) as usize;
+        let atom = remaining.get(..size).filter(|_| size >= 8)?;
+        remaining = &remaining[size..];
+        Some((&atom[4..8], &atom[8..]))
+    })
+}
+
+fn read_up_to(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut length = 0;
+
+    while length < buffer.len() {
+        match reader.read(&mut buffer[length..])? {
+            0 => break,
+            read => length += read,
+        }
+    }
+
+    Ok(length)
+}
+
+/// Reduces numbers such as `03/12` to `3` and dates to their year.
+fn normalize(tag: AudioTag, value: &str) -> Option<String> {
+    let value = value.trim();
+
+    match tag {
+        AudioTag::Track | AudioTag::Disc => {
+            let number: u32 = value.split('/').next()?.trim().parse().ok()?;
+            Some(number.to_string()).filter(|_| number > 0)
+        }
+        AudioTag::Year => value
+            .get(..4)
+            .filter(|year| year.chars().all(|c| c.is_ascii_digit()))
+            .map(String::from),
+        _ => Some(String::from(value)),
+    }
+}
+
+/// Tags often contain slashes (e.g. `AC/DC`), which would otherwise create directories.
+fn sanitize(value: &str) -> String {
+    let value: String = value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    if value.chars().all(|c| c == '.') {
+        value.replace('.', "_")
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn vorbis_comments(comments: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"mvr");
+        data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+
+        for comment in comments {
+            data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            data.extend_from_slice(comment.as_bytes());
+        }
+
+        data
+    }
+
+    fn atom(name: &[u8], contents: &[u8]) -> Vec<u8> {
+        let mut atom = ((contents.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend_from_slice(name);
+        atom.extend_from_slice(contents);
+        atom
+    }
+
+    fn tags(entries: &[(AudioTag, &str)]) -> AudioTags {
+        entries
+            .iter()
+            .map(|(tag, value)| (*tag, String::from(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn reads_flac_tags() {
+        let comments = vorbis_comments(&["ARTIST=AC/DC", "tracknumber=03/12", "Date=1980-07-25"]);
+
+        let mut flac = b"fLaC".to_vec();
+        flac.extend_from_slice(&[0x00, 0x00, 0x00, 0x02, 0xaa, 0xbb]);
+        flac.push(0x84);
+        flac.extend_from_slice(&(comments.len() as u32).to_be_bytes()[1..]);
+        flac.extend_from_slice(&comments);
+
+        assert_eq!(
+            tags(&[
+                (AudioTag::Artist, "AC_DC"),
+                (AudioTag::Track, "3"),
+                (AudioTag::Year, "1980"),
+            ]),
+            read_audio_tags_from(Cursor::new(flac)).unwrap()
+        );
+    }
+
+    #[test]
+    fn reads_ogg_tags() {
+        let identification = b"\x01vorbis".to_vec();
+        let mut comment_header = b"\x03vorbis".to_vec();
+        comment_header.extend(vorbis_comments(&["TITLE=Song", "ALBUM=Album"]));
+
+        let mut ogg = b"OggS\x00\x02".to_vec();
+        ogg.extend_from_slice(&[0x00; 20]);
+        ogg.push(2);
+        ogg.push(identification.len() as u8);
+        ogg.push(comment_header.len() as u8);
+        ogg.extend(identification);
+        ogg.extend(comment_header);
+
+        assert_eq!(
+            tags(&[(AudioTag::Title, "Song"), (AudioTag::Album, "Album")]),
+            read_audio_tags_from(Cursor::new(ogg)).unwrap()
+        );
+    }
+
+    #[test]
+    fn reads_mp4_tags() {
+        let text_item = |name: &[u8], value: &str| {
+            let mut data = vec![0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+            data.extend_from_slice(value.as_bytes());
+            atom(name, &atom(b"data", &data))
+        };
+        let track = atom(
+            b"trkn",
+            &atom(b"data", &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 0, 9, 0, 0]),
+        );
+
+        let mut ilst = text_item(b"\xa9nam", "Title");
+        ilst.extend(text_item(b"\xa9ART", "Artist"));
+        ilst.extend(track);
+
+        let mut meta = vec![0x00; 4];
+        meta.extend(atom(b"hdlr", &[0x00; 25]));
+        meta.extend(atom(b"ilst", &ilst));
+
+        let mut mp4 = atom(b"ftyp", b"M4A \x00\x00\x00\x00");
+        mp4.extend(atom(b"free", &[]));
+        mp4.extend(atom(b"moov", &atom(b"udta", &atom(b"meta", &meta))));
+
+        assert_eq!(
+            tags(&[
+                (AudioTag::Title, "Title"),
+                (AudioTag::Artist, "Artist"),
+                (AudioTag::Track, "7"),
+            ]),
+            read_audio_tags_from(Cursor::new(mp4)).unwrap()
+        );
+    }
+
+    #[test]
+    fn ignores_atoms_with_overflowing_sizes() {
+        let mut mp4 = atom(b"ftyp", b"M4A \x00\x00\x00\x00");
+        mp4.extend_from_slice(&1u32.to_be_bytes());
+        mp4.extend_from_slice(b"free");
+        mp4.extend_from_slice(&(u64::MAX - 4).to_be_bytes());
+        mp4.extend(atom(b"moov", &[]));
+
+        assert_eq!(
+            AudioTags::new(),
+            read_audio_tags_from(Cursor::new(mp4)).unwrap()
+        );
+    }
+
+    #[test]
+    fn reads_id3v1_tags() {
+        let field = |value: &str, length: usize| {
+            let mut field = value.as_bytes().to_vec();
+            field.resize(length, 0);
+            field
+        };
+
+        let mut mp3 = vec![0xff; 64];
+        mp3.extend_from_slice(b"TAG");
+        mp3.extend(field("Title", 30));
+        mp3.extend(field("Artist", 30));
+        mp3.extend(field("Album", 30));
+        mp3.extend(field("1999", 4));
+        mp3.extend(field("", 28));
+        mp3.extend_from_slice(&[0, 5, 255]);
+
+        assert_eq!(
+            tags(&[
+                (AudioTag::Title, "Title"),
+                (AudioTag::Artist, "Artist"),
+                (AudioTag::Album, "Album"),
+                (AudioTag::Track, "5"),
+                (AudioTag::Year, "1999"),
+            ]),
+            read_audio_tags_from(Cursor::new(mp3)).unwrap()
+        );
+    }
+
+    /// Small files with silent audio and the same tags in each format.
+    fn read_fixture(data: &[u8]) -> AudioTags {
+        read_audio_tags_from(Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn reads_tags_of_audio_files() {
+        let expected = tags(&[
+            (AudioTag::Title, "Früh"),
+            (AudioTag::Artist, "Quiet_Loud"),
+            (AudioTag::AlbumArtist, "Various Artists"),
+            (AudioTag::Album, "Fixtures"),
+            (AudioTag::Track, "2"),
+            (AudioTag::Disc, "1"),
+            (AudioTag::Year, "2021"),
+            (AudioTag::Genre, "Rock"),
+        ]);
+
+        // The genre of the MP3 file is only in its ID3v1 tag
+        assert_eq!(
+            expected,
+            read_fixture(include_bytes!("../testdata/audio/tagged.mp3"))
+        );
+        assert_eq!(
+            expected,
+            read_fixture(include_bytes!("../testdata/audio/tagged.flac"))
+        );
+        assert_eq!(
+            expected,
+            read_fixture(include_bytes!("../testdata/audio/tagged.opus"))
+        );
+        assert_eq!(
+            expected,
+            read_fixture(include_bytes!("../testdata/audio/tagged.m4a"))
+        );
+    }
+
+    #[test]
+    fn reads_id3v24_tags() {
+        let text_frame = |id: &[u8], text: &[u8]| {
+            let mut frame = id.to_vec();
+            frame.extend_from_slice(&[0, 0, 0, text.len() as u8 + 1, 0, 0, 3]);
+            frame.extend_from_slice(text);
+            frame
+        };
+
+        let mut frames = text_frame(b"TIT2", "Première\0Second".as_bytes());
+        frames.extend(text_frame(b"TCON", b"(9)"));
+        frames.extend(text_frame(b"TDRC", b"1999-12-31"));
+        frames.extend(text_frame(b"TRCK", b"07"));
+        frames.extend_from_slice(&[0; 16]);
+
+        let mut mp3 = b"ID3\x04\x00\x00".to_vec();
+        mp3.extend_from_slice(&[0, 0, 0, frames.len() as u8]);
+        mp3.extend(frames);
+        mp3.extend_from_slice(&[0xff, 0xfb, 0x90, 0xc4]);
+
+        assert_eq!(
+            tags(&[
+                (AudioTag::Title, "Première"),
+                (AudioTag::Genre, "Metal"),
+                (AudioTag::Year, "1999"),
+                (AudioTag::Track, "7"),
+            ]),
+            read_audio_tags_from(Cursor::new(mp3)).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_id3_genres() {
+        assert_eq!(Some(String::from("Rock")), parse_id3_genre("(17)"));
+        assert_eq!(Some(String::from("Rock")), parse_id3_genre("17"));
+        assert_eq!(Some(String::from("Hard")), parse_id3_genre("(17)Hard"));
+        assert_eq!(
+            Some(String::from("Synthwave")),
+            parse_id3_genre("Synthwave")
+        );
+        assert_eq!(None, parse_id3_genre("(200)"));
+    }
+
+    #[test]
+    fn removes_unsynchronisation() {
+        assert_eq!(
+            vec![0xff, 0xe0, 0xff, 0x00, 0x01],
+            resynchronize(&[0xff, 0x00, 0xe0, 0xff, 0x00, 0x00, 0x01])
+        );
+    }
+
+    #[test]
+    fn reads_no_tags_from_other_files() {
+        assert_eq!(
+            HashMap::new(),
+            read_audio_tags_from(Cursor::new(b"plain text".to_vec())).unwrap()
+        );
+    }
+
+    #[test]
+    fn sanitizes_values() {
+        assert_eq!("AC_DC", sanitize(" AC/DC "));
+        assert_eq!("a_b", sanitize("a\nb"));
+        assert_eq!("__", sanitize(".."));
+        assert_eq!("...And Justice", sanitize("...And Justice"));
+    }
+}
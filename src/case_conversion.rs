@@ -0,0 +1,140 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Case {
+    Upper,
+    Lower,
+    Title,
+    Snake,
+    Kebab,
+    Camel,
+    Pascal,
+}
+
+pub(crate) fn convert_case(input: &str, case: Case) -> String {
+    match case {
+        Case::Upper => input.to_uppercase(),
+        Case::Lower => input.to_lowercase(),
+        Case::Title => map_words(input, " ", capitalize),
+        Case::Snake => map_words(input, "_", str::to_lowercase),
+        Case::Kebab => map_words(input, "-", str::to_lowercase),
+        Case::Pascal => map_words(input, "", capitalize),
+        Case::Camel => {
+            let pascal_case = map_words(input, "", capitalize);
+            let mut chars = pascal_case.chars();
+            match chars.next() {
+                Some(first) => first.to_lowercase().chain(chars).collect(),
+                None => pascal_case,
+            }
+        }
+    }
+}
+
+fn map_words(input: &str, separator: &str, transform: impl Fn(&str) -> String) -> String {
+    split_words(input)
+        .into_iter()
+        .map(transform)
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// Splits the input into words at separators (anything that is not alphanumeric)
+/// and at case boundaries, e.g. `"myHTTPServer_v2"` becomes `["my", "HTTP", "Server", "v2"]`.
+pub(crate) fn split_words(input: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+
+    for segment in input.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<(usize, char)> = segment.char_indices().collect();
+        let mut word_start = 0;
+
+        for (position, &(index, current)) in chars.iter().enumerate().skip(1) {
+            let previous = chars[position - 1].1;
+            let next = chars.get(position + 1).map(|&(_, next)| next);
+
+            let starts_word = current.is_uppercase()
+                && (previous.is_lowercase()
+                    || previous.is_numeric()
+                    || (previous.is_uppercase() && next.is_some_and(char::is_lowercase)));
+
+            if starts_word {
+                words.push(&segment[word_start..index]);
+                word_start = index;
+            }
+        }
+
+        words.push(&segment[word_start..]);
+    }
+
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_words_at_separators() {
+        assert_eq!(
+            vec!["My", "Holiday", "Photo"],
+            split_words("My Holiday_Photo-")
+        );
+    }
+
+    #[test]
+    fn splits_words_at_case_boundaries() {
+        assert_eq!(
+            vec!["my", "HTTP", "Server", "v2", "File"],
+            split_words("myHTTPServer v2File")
+        );
+    }
+
+    #[test]
+    fn converts_to_simple_cases() {
+        assert_eq!("MY PHOTO", convert_case("My Photo", Case::Upper));
+        assert_eq!("my photo", convert_case("My Photo", Case::Lower));
+        assert_eq!(
+            "My Holiday Photo",
+            convert_case("my_holiday-PHOTO", Case::Title)
+        );
+    }
+
+    #[test]
+    fn converts_to_separated_cases() {
+        assert_eq!(
+            "my_holiday_photo",
+            convert_case("My Holiday Photo", Case::Snake)
+        );
+        assert_eq!(
+            "my-holiday-photo",
+            convert_case("myHolidayPhoto", Case::Kebab)
+        );
+    }
+
+    #[test]
+    fn converts_to_camel_and_pascal_case() {
+        assert_eq!(
+            "myHolidayPhoto",
+            convert_case("my-holiday photo", Case::Camel)
+        );
+        assert_eq!(
+            "MyHolidayPhoto",
+            convert_case("my_holiday_photo", Case::Pascal)
+        );
+    }
+
+    #[test]
+    fn converts_empty_input() {
+        assert_eq!("", convert_case("", Case::Camel));
+        assert_eq!("", convert_case("__", Case::Snake));
+    }
+}
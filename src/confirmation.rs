@@ -0,0 +1,122 @@
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+
+#[cfg(test)]
+use mockiato::mockable;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Answer {
+    Yes,
+    No,
+    /// Rename this and all of the remaining files without asking
+    All,
+    /// Skip this and all of the remaining files
+    Quit,
+    /// Rename the file to a different name
+    Edit(String),
+}
+
+#[cfg_attr(test, mockable)]
+pub(crate) trait Prompter: Debug {
+    fn confirm(&self, old_name: &str, new_name: &str) -> io::Result<Answer>;
+
+    /// Tells the user why an answer could not be accepted before asking again.
+    fn reject(&self, reason: &str);
+}
+
+/// Asks on the controlling terminal, so that answers can be given
+/// even if the standard input is redirected.
+#[derive(Debug, Default)]
+pub(crate) struct TerminalPrompter {
+    /// Opened on the first question and kept, so that answers typed ahead are not lost
+    terminal: RefCell<Option<BufReader<File>>>,
+}
+
+const TERMINAL: &str = "/dev/tty";
+
+impl TerminalPrompter {
+    fn ask(&self, question: &str) -> io::Result<Option<String>> {
+        eprint!("{}", question);
+        io::stderr().flush()?;
+
+        let mut terminal = self.terminal.borrow_mut();
+        if terminal.is_none() {
+            *terminal = Some(BufReader::new(File::open(TERMINAL)?));
+        }
+
+        let mut line = String::new();
+        let length = terminal
+            .as_mut()
+            .expect("The terminal is open")
+            .read_line(&mut line)?;
+
+        Ok(if length == 0 {
+            None
+        } else {
+            Some(String::from(line.trim_end_matches(['\r', '\n'])))
+        })
+    }
+}
+
+impl Prompter for TerminalPrompter {
+    fn confirm(&self, old_name: &str, new_name: &str) -> io::Result<Answer> {
+        let question = format!(
+            "Rename {:?} → {:?}? [y]es, [n]o, [a]ll, [q]uit, [e]dit: ",
+            old_name, new_name
+        );
+
+        loop {
+            let input = match self.ask(&question)? {
+                Some(input) => input,
+                None => return Ok(Answer::Quit),
+            };
+
+            match parse_answer(&input) {
+                Some(Answer::Edit(_)) => {
+                    match self.ask("New name: ")? {
+                        Some(name) if !name.is_empty() => return Ok(Answer::Edit(name)),
+                        Some(_) => continue,
+                        None => return Ok(Answer::Quit),
+                    };
+                }
+                Some(answer) => return Ok(answer),
+                None => eprintln!("Please answer y, n, a, q or e"),
+            }
+        }
+    }
+
+    fn reject(&self, reason: &str) {
+        eprintln!("{}", reason);
+    }
+}
+
+/// Parses everything but the new name of an edit, which is asked for separately.
+fn parse_answer(input: &str) -> Option<Answer> {
+    match input.trim().to_lowercase().as_str() {
+        "y" | "yes" => Some(Answer::Yes),
+        "n" | "no" => Some(Answer::No),
+        "a" | "all" => Some(Answer::All),
+        "q" | "quit" => Some(Answer::Quit),
+        "e" | "edit" => Some(Answer::Edit(String::new())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_answers() {
+        assert_eq!(Some(Answer::Yes), parse_answer("y"));
+        assert_eq!(Some(Answer::No), parse_answer(" No "));
+        assert_eq!(Some(Answer::All), parse_answer("a"));
+        assert_eq!(Some(Answer::Quit), parse_answer("quit"));
+        assert_eq!(Some(Answer::Edit(String::new())), parse_answer("E"));
+        assert_eq!(None, parse_answer(""));
+        assert_eq!(None, parse_answer("maybe"));
+    }
+}
@@ -0,0 +1,30 @@
+use std::fmt::Debug;
+use std::io;
+use std::io::Write;
+
+/// Where results and diagnostics are written. Results can be machine readable,
+/// e.g. plans or scripts, so diagnostics must never be mixed into them.
+pub(crate) trait Console: Debug {
+    /// Writes the text as is, e.g. a rename, a plan or a script.
+    fn write_output(&self, text: &str);
+
+    /// Writes the message followed by a line break, e.g. why a file is skipped.
+    fn report(&self, message: &str);
+}
+
+/// Writes results to the standard output and diagnostics to the standard error.
+#[derive(Debug, Default)]
+pub(crate) struct StandardConsole;
+
+impl Console for StandardConsole {
+    fn write_output(&self, text: &str) {
+        let mut stdout = io::stdout();
+        let _ = stdout
+            .write_all(text.as_bytes())
+            .and_then(|_| stdout.flush());
+    }
+
+    fn report(&self, message: &str) {
+        eprintln!("{}", message);
+    }
+}
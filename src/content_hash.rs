@@ -0,0 +1,100 @@
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum HashAlgorithm {
+    Sha256,
+    Sha1,
+    Blake3,
+    Crc32,
+}
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Hashes the contents of a file without reading it into memory at once.
+pub(crate) fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    hash_reader(File::open(path)?, algorithm)
+}
+
+/// Returns the lowercase hexadecimal digest of everything the reader yields.
+pub(crate) fn hash_reader(reader: impl Read, algorithm: HashAlgorithm) -> io::Result<String> {
+    match algorithm {
+        HashAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            read_chunks(reader, |chunk| hasher.update(chunk))?;
+            Ok(to_hex(&hasher.finalize()))
+        }
+        HashAlgorithm::Sha1 => {
+            let mut hasher = Sha1::new();
+            read_chunks(reader, |chunk| hasher.update(chunk))?;
+            Ok(to_hex(&hasher.finalize()))
+        }
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            read_chunks(reader, |chunk| {
+                hasher.update(chunk);
+            })?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+        HashAlgorithm::Crc32 => {
+            let mut hasher = crc32fast::Hasher::new();
+            read_chunks(reader, |chunk| hasher.update(chunk))?;
+            Ok(format!("{:08x}", hasher.finalize()))
+        }
+    }
+}
+
+fn read_chunks(mut reader: impl Read, mut consume: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buffer = vec![0; BUFFER_SIZE];
+
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(length) => consume(&buffer[..length]),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_contents() {
+        let hash = |algorithm| hash_reader(&b"abc"[..], algorithm).unwrap();
+
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            hash(HashAlgorithm::Sha256)
+        );
+        assert_eq!(
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+            hash(HashAlgorithm::Sha1)
+        );
+        assert_eq!(
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            hash(HashAlgorithm::Blake3)
+        );
+        assert_eq!("352441c2", hash(HashAlgorithm::Crc32));
+    }
+
+    #[test]
+    fn hashes_input_larger_than_the_buffer() {
+        let contents = vec![b'a'; BUFFER_SIZE * 2 + 1];
+
+        assert_eq!(
+            format!("{:08x}", crc32fast::hash(&contents)),
+            hash_reader(&contents[..], HashAlgorithm::Crc32).unwrap()
+        );
+    }
+}
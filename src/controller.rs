@@ -1,5 +1,5 @@
 use crate::renamer::Renamer;
-use crate::{matching_pattern, replacement_pattern};
+use crate::{matching_pattern, metadata_filter, replacement_pattern};
 use nameof::name_of;
 use std::error::Error;
 use std::fmt;
@@ -11,16 +11,23 @@ pub(crate) trait Controller: Debug {
         &self,
         matching_pattern: &str,
         replacement_pattern: &str,
+        exclusion_patterns: &[&str],
+        filter_expression: Option<&str>,
         directory: &Path,
     ) -> Result<(), Box<dyn Error>>;
 }
 
-pub(crate) type RenamerFactory =
-    dyn Fn(matching_pattern::Pattern, replacement_pattern::Pattern) -> Box<dyn Renamer>;
+pub(crate) type RenamerFactory = dyn Fn(
+    matching_pattern::Pattern,
+    replacement_pattern::Pattern,
+    Vec<matching_pattern::Pattern>,
+    Option<metadata_filter::Expression>,
+) -> Box<dyn Renamer>;
 
 pub(crate) struct ControllerImpl {
     matching_pattern_parser: Box<dyn matching_pattern::Parser>,
     replacement_pattern_parser: Box<dyn replacement_pattern::Parser>,
+    metadata_filter_parser: Box<dyn metadata_filter::Parser>,
     renamer_factory: Box<RenamerFactory>,
 }
 
@@ -28,11 +35,13 @@ impl ControllerImpl {
     pub(crate) fn new(
         matching_pattern_parser: Box<dyn matching_pattern::Parser>,
         replacement_pattern_parser: Box<dyn replacement_pattern::Parser>,
+        metadata_filter_parser: Box<dyn metadata_filter::Parser>,
         renamer_factory: Box<RenamerFactory>,
     ) -> Self {
         Self {
             matching_pattern_parser,
             replacement_pattern_parser,
+            metadata_filter_parser,
             renamer_factory,
         }
     }
@@ -43,12 +52,26 @@ impl Controller for ControllerImpl {
         &self,
         matching_pattern: &str,
         replacement_pattern: &str,
+        exclusion_patterns: &[&str],
+        filter_expression: Option<&str>,
         directory: &Path,
     ) -> Result<(), Box<dyn Error>> {
         let matching_pattern = self.matching_pattern_parser.parse(matching_pattern)?;
         let replacement_pattern = self.replacement_pattern_parser.parse(replacement_pattern)?;
-
-        let renamer = (self.renamer_factory)(matching_pattern, replacement_pattern);
+        let exclusion_patterns = exclusion_patterns
+            .iter()
+            .map(|pattern| self.matching_pattern_parser.parse(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let filter_expression = filter_expression
+            .map(|expression| self.metadata_filter_parser.parse(expression))
+            .transpose()?;
+
+        let renamer = (self.renamer_factory)(
+            matching_pattern,
+            replacement_pattern,
+            exclusion_patterns,
+            filter_expression,
+        );
 
         renamer.rename_files_in_directory(directory)
     }
@@ -65,6 +88,10 @@ impl Debug for ControllerImpl {
                 name_of!(replacement_pattern_parser in ControllerImpl),
                 &self.replacement_pattern_parser,
             )
+            .field(
+                name_of!(metadata_filter_parser in ControllerImpl),
+                &self.metadata_filter_parser,
+            )
             .finish()
     }
 }
@@ -74,6 +101,7 @@ mod tests {
     use super::*;
     use crate::renamer::RenamerMock;
     use std::path::PathBuf;
+    use std::time::Duration;
 
     #[test]
     fn test() {
@@ -86,6 +114,14 @@ mod tests {
         matching_pattern_parser
             .expect_parse(|arg| arg.partial_eq("foo"))
             .returns(Ok(expected_matching_pattern.clone()));
+        let expected_exclusion_pattern = matching_pattern::Pattern {
+            elements: vec![matching_pattern::Element::Token(
+                matching_pattern::Token::Text(String::from("qux")),
+            )],
+        };
+        matching_pattern_parser
+            .expect_parse(|arg| arg.partial_eq("qux"))
+            .returns(Ok(expected_exclusion_pattern.clone()));
 
         let mut replacement_pattern_parser = replacement_pattern::ParserMock::new();
         let expected_replacement_pattern = replacement_pattern::Pattern {
@@ -95,10 +131,22 @@ mod tests {
             .expect_parse(|arg| arg.partial_eq("bar"))
             .returns(Ok(expected_replacement_pattern.clone()));
 
-        let renamer_factory: Box<RenamerFactory> =
-            Box::new(move |matching_pattern, replacement_pattern| {
+        let mut metadata_filter_parser = metadata_filter::ParserMock::new();
+        let expected_filter_expression =
+            metadata_filter::Expression::Condition(metadata_filter::Condition::Age(
+                metadata_filter::Operator::Greater,
+                Duration::from_secs(60),
+            ));
+        metadata_filter_parser
+            .expect_parse(|arg| arg.partial_eq("age > 1m"))
+            .returns(Ok(expected_filter_expression.clone()));
+
+        let renamer_factory: Box<RenamerFactory> = Box::new(
+            move |matching_pattern, replacement_pattern, exclusion_patterns, filter_expression| {
                 assert_eq!(expected_matching_pattern, matching_pattern);
                 assert_eq!(expected_replacement_pattern, replacement_pattern);
+                assert_eq!(vec![expected_exclusion_pattern.clone()], exclusion_patterns);
+                assert_eq!(Some(expected_filter_expression.clone()), filter_expression);
 
                 let mut renamer = RenamerMock::new();
                 renamer
@@ -106,16 +154,24 @@ mod tests {
                     .returns_once(Ok(()));
 
                 Box::new(renamer)
-            });
+            },
+        );
 
         let controller = ControllerImpl::new(
             Box::new(matching_pattern_parser),
             Box::new(replacement_pattern_parser),
+            Box::new(metadata_filter_parser),
             renamer_factory,
         );
 
         controller
-            .rename_files_by_pattern("foo", "bar", &PathBuf::from("baz"))
+            .rename_files_by_pattern(
+                "foo",
+                "bar",
+                &["qux"],
+                Some("age > 1m"),
+                &PathBuf::from("baz"),
+            )
             .unwrap();
     }
 }
@@ -0,0 +1,524 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+
+/// Moves a file from the source to the target, both relative to the root directory.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Rename {
+    pub(crate) source: PathBuf,
+    pub(crate) target: PathBuf,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Step {
+    /// A rename whose target is not taken by another file of the batch
+    Move(Rename),
+    /// Renames where each target is the source of the next one, and the last
+    /// target is the source of the first one, e.g. two files that swap their names
+    Cycle(Vec<Rename>),
+}
+
+impl Step {
+    pub(crate) fn renames(&self) -> &[Rename] {
+        match self {
+            Step::Move(rename) => std::slice::from_ref(rename),
+            Step::Cycle(renames) => renames,
+        }
+    }
+}
+
+/// Orders the renames so that files are moved out of the way before other files take their
+/// place, e.g. `b → c` runs before `a → b`. Renames that form a cycle become a single step.
+pub(crate) fn plan_steps(renames: Vec<Rename>) -> Vec<Step> {
+    let index_by_source: HashMap<_, _> = renames
+        .iter()
+        .enumerate()
+        .map(|(index, rename)| (rename.source.clone(), index))
+        .collect();
+    let dependency = |index: usize| {
+        index_by_source
+            .get(&renames[index].target)
+            .copied()
+            .filter(|dependency| *dependency != index)
+    };
+
+    let mut is_planned = vec![false; renames.len()];
+    let mut steps = Vec::new();
+
+    for start in 0..renames.len() {
+        let mut chain: Vec<usize> = Vec::new();
+        let mut next = Some(start);
+
+        while let Some(index) = next.filter(|index| !is_planned[*index]) {
+            if let Some(position) = chain.iter().position(|chained| *chained == index) {
+                let cycle = chain.split_off(position);
+                for index in &cycle {
+                    is_planned[*index] = true;
+                }
+
+                steps.push(Step::Cycle(
+                    cycle.iter().map(|index| renames[*index].clone()).collect(),
+                ));
+                break;
+            }
+
+            chain.push(index);
+            next = dependency(index);
+        }
+
+        for index in chain.into_iter().rev() {
+            is_planned[index] = true;
+            steps.push(Step::Move(renames[index].clone()));
+        }
+    }
+
+    steps
+}
+
+/// Performs renames relative to a directory file descriptor, so that replacing
+/// directories with symlinks while renaming cannot redirect files elsewhere.
+#[derive(Debug)]
+pub(crate) struct Executor {
+    root: OwnedFd,
+    allow_escape: bool,
+}
+
+impl Executor {
+    /// Paths only lead outside of the root, through absolute paths, `..` or symlinks,
+    /// if `allow_escape` is set.
+    pub(crate) fn open(root: &Path, allow_escape: bool) -> io::Result<Self> {
+        Ok(Self {
+            root: open_directory(libc::AT_FDCWD, root.as_os_str().as_bytes(), true)?,
+            allow_escape,
+        })
+    }
+
+    /// Fails with `io::ErrorKind::AlreadyExists` instead of replacing existing files.
+    pub(crate) fn execute(&self, step: &Step) -> io::Result<()> {
+        match step {
+            Step::Move(rename) => {
+                let source = self.resolve(&rename.source, false)?;
+                let target = self.resolve(&rename.target, true)?;
+                move_without_replacing(&source, &target)
+            }
+            Step::Cycle(renames) => {
+                // Exchanging the first source with every other source in turn puts each
+                // file in the place of the next one
+                let first = self.resolve(&renames[0].source, false)?;
+                for rename in &renames[1..] {
+                    exchange(&first, &self.resolve(&rename.source, false)?)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Opens the parent directory of the path one component at a time.
+    fn resolve(&self, path: &Path, create_directories: bool) -> io::Result<Location> {
+        let file_name = match path.file_name() {
+            Some(file_name) => CString::new(file_name.as_bytes())?,
+            None => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+        };
+
+        let mut directory: Option<OwnedFd> = None;
+
+        for component in path.parent().into_iter().flat_map(Path::components) {
+            let current = directory
+                .as_ref()
+                .map_or(self.root.as_raw_fd(), |fd| fd.as_raw_fd());
+
+            directory = match component {
+                Component::Prefix(_) | Component::RootDir if self.allow_escape => {
+                    Some(open_directory(libc::AT_FDCWD, b"/", true)?)
+                }
+                Component::Prefix(_) | Component::RootDir => return Err(outside_of_root(path)),
+                Component::CurDir => continue,
+                Component::ParentDir => {
+                    let parent = open_directory(current, b"..", true)?;
+                    if !self.allow_escape && !self.contains(&parent)? {
+                        return Err(outside_of_root(path));
+                    }
+
+                    Some(parent)
+                }
+                Component::Normal(name) => {
+                    let name = name.as_bytes();
+                    match self.open_child(current, name) {
+                        Err(error)
+                            if create_directories && error.kind() == io::ErrorKind::NotFound =>
+                        {
+                            create_directory(current, name)?;
+                            Some(self.open_child(current, name)?)
+                        }
+                        result => Some(result?),
+                    }
+                }
+            };
+        }
+
+        let directory = match directory {
+            Some(directory) => directory,
+            None => self.root.try_clone()?,
+        };
+
+        Ok(Location {
+            directory,
+            file_name,
+        })
+    }
+}
+
+impl Executor {
+    /// Opens a directory within the parent. Symlinks are followed as long as
+    /// the directory they lead to is inside of the root.
+    fn open_child(&self, parent: RawFd, name: &[u8]) -> io::Result<OwnedFd> {
+        match open_directory(parent, name, self.allow_escape) {
+            // Symlinks are reported as loops or, with O_DIRECTORY, as not being directories
+            Err(error)
+                if matches!(
+                    error.raw_os_error(),
+                    Some(libc::ELOOP) | Some(libc::ENOTDIR)
+                ) =>
+            {
+                let directory = open_directory(parent, name, true)?;
+
+                if self.contains(&directory)? {
+                    Ok(directory)
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!(
+                            "{:?} is a symlink to a directory outside of the current directory",
+                            String::from_utf8_lossy(name)
+                        ),
+                    ))
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Whether the directory is the root or inside of it, found by walking up through `..`.
+    fn contains(&self, directory: &OwnedFd) -> io::Result<bool> {
+        let root = file_id(self.root.as_raw_fd())?;
+        let mut current = directory.try_clone()?;
+
+        loop {
+            let id = file_id(current.as_raw_fd())?;
+            if id == root {
+                return Ok(true);
+            }
+
+            let parent = open_directory(current.as_raw_fd(), b"..", true)?;
+            // Only the file system root is its own parent
+            if file_id(parent.as_raw_fd())? == id {
+                return Ok(false);
+            }
+
+            current = parent;
+        }
+    }
+}
+
+fn outside_of_root(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{:?} leads outside of the current directory", path),
+    )
+}
+
+/// A file name within an opened directory.
+struct Location {
+    directory: OwnedFd,
+    file_name: CString,
+}
+
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+fn open_directory(parent: RawFd, name: &[u8], follow_symlinks: bool) -> io::Result<OwnedFd> {
+    let name = CString::new(name)?;
+    let mut flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+    if !follow_symlinks {
+        flags |= libc::O_NOFOLLOW;
+    }
+
+    let fd = check(unsafe { libc::openat(parent, name.as_ptr(), flags) })?;
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn create_directory(parent: RawFd, name: &[u8]) -> io::Result<()> {
+    let name = CString::new(name)?;
+
+    match check(unsafe { libc::mkdirat(parent, name.as_ptr(), 0o777) }) {
+        Err(error) if error.kind() != io::ErrorKind::AlreadyExists => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// The device and inode number of the open file.
+fn file_id(fd: RawFd) -> io::Result<(libc::dev_t, libc::ino_t)> {
+    let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+    check(unsafe { libc::fstat(fd, stat.as_mut_ptr()) })?;
+    let stat = unsafe { stat.assume_init() };
+
+    Ok((stat.st_dev, stat.st_ino))
+}
+
+fn exists(location: &Location) -> io::Result<bool> {
+    let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+    let result = unsafe {
+        libc::fstatat(
+            location.directory.as_raw_fd(),
+            location.file_name.as_ptr(),
+            stat.as_mut_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    };
+
+    match check(result) {
+        Ok(_) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+fn rename_at(source: &Location, target: &Location) -> io::Result<()> {
+    check(unsafe {
+        libc::renameat(
+            source.directory.as_raw_fd(),
+            source.file_name.as_ptr(),
+            target.directory.as_raw_fd(),
+            target.file_name.as_ptr(),
+        )
+    })
+    .map(|_| ())
+}
+
+#[cfg(target_os = "linux")]
+fn rename_at_with_flags(
+    source: &Location,
+    target: &Location,
+    flags: libc::c_uint,
+) -> io::Result<()> {
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            source.directory.as_raw_fd(),
+            source.file_name.as_ptr(),
+            target.directory.as_raw_fd(),
+            target.file_name.as_ptr(),
+            flags,
+        )
+    };
+
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rename_at_with_flags(_: &Location, _: &Location, _: libc::c_uint) -> io::Result<()> {
+    Err(io::Error::from_raw_os_error(libc::ENOSYS))
+}
+
+/// Whether the kernel or the file system does not support the flags of `renameat2`.
+fn is_unsupported(error: &io::Error) -> bool {
+    matches!(
+        error.raw_os_error(),
+        Some(libc::EINVAL) | Some(libc::ENOSYS) | Some(libc::ENOTSUP)
+    )
+}
+
+const RENAME_NOREPLACE: libc::c_uint = 1;
+const RENAME_EXCHANGE: libc::c_uint = 2;
+
+fn move_without_replacing(source: &Location, target: &Location) -> io::Result<()> {
+    match rename_at_with_flags(source, target, RENAME_NOREPLACE) {
+        Err(error) if is_unsupported(&error) => {
+            // Not atomic, but the best that can be done without support for the flag
+            if exists(target)? {
+                Err(io::Error::from(io::ErrorKind::AlreadyExists))
+            } else {
+                rename_at(source, target)
+            }
+        }
+        result => result,
+    }
+}
+
+fn exchange(first: &Location, second: &Location) -> io::Result<()> {
+    match rename_at_with_flags(first, second, RENAME_EXCHANGE) {
+        Err(error) if is_unsupported(&error) => {
+            let temporary = Location {
+                directory: first.directory.try_clone()?,
+                file_name: CString::new(format!(".mvr-{}.tmp", process::id()))?,
+            };
+
+            move_without_replacing(first, &temporary)?;
+            move_without_replacing(second, first)?;
+            move_without_replacing(&temporary, second)
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+    use std::os::unix::fs::symlink;
+
+    fn rename(source: &str, target: &str) -> Rename {
+        Rename {
+            source: PathBuf::from(source),
+            target: PathBuf::from(target),
+        }
+    }
+
+    #[test]
+    fn plans_independent_renames_in_order() {
+        assert_eq!(
+            vec![Step::Move(rename("a", "x")), Step::Move(rename("b", "y")),],
+            plan_steps(vec![rename("a", "x"), rename("b", "y")])
+        );
+    }
+
+    #[test]
+    fn plans_chains_from_the_end() {
+        assert_eq!(
+            vec![
+                Step::Move(rename("c", "d")),
+                Step::Move(rename("b", "c")),
+                Step::Move(rename("a", "b")),
+            ],
+            plan_steps(vec![rename("a", "b"), rename("b", "c"), rename("c", "d")])
+        );
+    }
+
+    #[test]
+    fn plans_cycles_as_single_steps() {
+        assert_eq!(
+            vec![
+                Step::Cycle(vec![rename("a", "b"), rename("b", "c"), rename("c", "a")]),
+                Step::Move(rename("d", "a")),
+            ],
+            plan_steps(vec![
+                rename("d", "a"),
+                rename("a", "b"),
+                rename("b", "c"),
+                rename("c", "a"),
+            ])
+        );
+    }
+
+    #[test]
+    fn executes_steps() {
+        let directory = temp_dir().join(format!("mvr-executor-{}", process::id()));
+        create_dir_all(directory.join("outside")).unwrap();
+        let root = directory.join("root");
+        create_dir_all(&root).unwrap();
+        symlink("../outside", root.join("link")).unwrap();
+        for name in &["a", "b", "c", "d"] {
+            write(root.join(name), name).unwrap();
+        }
+
+        let executor = Executor::open(&root, false).unwrap();
+        let cycle = executor.execute(&Step::Cycle(vec![
+            rename("a", "b"),
+            rename("b", "c"),
+            rename("c", "a"),
+        ]));
+        let existing = executor.execute(&Step::Move(rename("d", "a")));
+        let symlinked = executor.execute(&Step::Move(rename("d", "link/d")));
+        let created = executor.execute(&Step::Move(rename("d", "new/d")));
+        let contents: Vec<_> = ["a", "b", "c", "new/d"]
+            .iter()
+            .map(|name| read_to_string(root.join(name)).unwrap())
+            .collect();
+
+        remove_dir_all(&directory).unwrap();
+
+        assert!(cycle.is_ok());
+        assert_eq!(io::ErrorKind::AlreadyExists, existing.unwrap_err().kind());
+        assert!(symlinked.is_err());
+        assert!(created.is_ok());
+        assert_eq!(vec!["c", "a", "b", "d"], contents);
+    }
+
+    #[test]
+    fn follows_symlinks_inside_the_root() {
+        let directory = temp_dir().join(format!("mvr-executor-symlinks-{}", process::id()));
+        create_dir_all(directory.join("outside")).unwrap();
+        let root = directory.join("root");
+        create_dir_all(root.join("real")).unwrap();
+        symlink("real", root.join("inside")).unwrap();
+        symlink("../outside", root.join("outside")).unwrap();
+        write(root.join("real/a"), "a").unwrap();
+        write(directory.join("outside/b"), "b").unwrap();
+
+        let executor = Executor::open(&root, false).unwrap();
+        let inside = executor.execute(&Step::Move(rename("inside/a", "inside/c")));
+        let outside = executor.execute(&Step::Move(rename("outside/b", "b")));
+        let renamed = root.join("real/c").exists();
+
+        remove_dir_all(&directory).unwrap();
+
+        assert!(inside.is_ok());
+        assert!(renamed);
+        let error = outside.unwrap_err();
+        assert_eq!(io::ErrorKind::PermissionDenied, error.kind());
+        assert!(error
+            .to_string()
+            .contains("outside of the current directory"));
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_root() {
+        let directory = temp_dir().join(format!("mvr-executor-escape-{}", process::id()));
+        let root = directory.join("root");
+        create_dir_all(root.join("dir")).unwrap();
+        write(root.join("a"), "a").unwrap();
+        let absolute_target = directory.join("absolute");
+
+        let executor = Executor::open(&root, false).unwrap();
+        let parent = executor.execute(&Step::Move(rename("a", "dir/../../escaped")));
+        let absolute = executor.execute(&Step::Move(Rename {
+            source: PathBuf::from("a"),
+            target: absolute_target.clone(),
+        }));
+        let inside = executor.execute(&Step::Move(rename("a", "dir/../b")));
+        let escaping = Executor::open(&root, true)
+            .unwrap()
+            .execute(&Step::Move(rename("b", "../escaped")));
+        let files = (
+            root.join("b").exists(),
+            directory.join("escaped").exists(),
+            absolute_target.exists(),
+        );
+
+        remove_dir_all(&directory).unwrap();
+
+        assert_eq!(io::ErrorKind::PermissionDenied, parent.unwrap_err().kind());
+        assert_eq!(
+            io::ErrorKind::PermissionDenied,
+            absolute.unwrap_err().kind()
+        );
+        assert!(inside.is_ok());
+        assert!(escaping.is_ok());
+        assert_eq!((false, true, false), files);
+    }
+}
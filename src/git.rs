@@ -0,0 +1,366 @@
+use crate::executor::Rename;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::io;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Debug)]
+pub(crate) enum GitError {
+    IoError(io::Error),
+    NotARepository(PathBuf),
+    CommandFailed { command: String, message: String },
+    InvalidOutput(&'static str),
+}
+
+impl Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitError::IoError(error) => write!(f, "Unable to run git: {}", error),
+            GitError::NotARepository(directory) => {
+                write!(f, "{:?} is not inside a git repository", directory)
+            }
+            GitError::CommandFailed { command, message } => {
+                write!(f, "git {} failed: {}", command, message.trim_end())
+            }
+            GitError::InvalidOutput(command) => write!(f, "Unexpected output of git {}", command),
+        }
+    }
+}
+
+impl Error for GitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GitError::IoError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A file in the index, with its path relative to the top level of the repository.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct IndexEntry {
+    mode: String,
+    object: String,
+    stage: String,
+    path: String,
+}
+
+impl IndexEntry {
+    /// Parses an entry as printed by `git ls-files -s`, e.g. `100644 <object> 0\tpath`.
+    fn parse(line: &str) -> Option<Self> {
+        let (info, path) = line.split_once('\t')?;
+        let mut fields = info.split(' ');
+
+        Some(Self {
+            mode: String::from(fields.next()?),
+            object: String::from(fields.next()?),
+            stage: String::from(fields.next()?),
+            path: String::from(path),
+        })
+    }
+
+    /// Whether the entry is the file at the path or inside the directory at the path.
+    fn is_within(&self, path: &str) -> bool {
+        self.path
+            .strip_prefix(path)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+/// The repository that contains a directory, accessed through the `git` command.
+#[derive(Debug)]
+pub(crate) struct Repository {
+    top_level: PathBuf,
+    /// The path of the directory relative to the top level, e.g. `src/`
+    prefix: PathBuf,
+    entries: Vec<IndexEntry>,
+    changed_paths: HashSet<String>,
+}
+
+impl Repository {
+    /// Finds the repository and reads its index.
+    pub(crate) fn open(directory: &Path) -> Result<Self, GitError> {
+        let top_level = run(directory, &["rev-parse", "--show-toplevel"], None)
+            .map_err(|_| GitError::NotARepository(directory.to_path_buf()))?;
+        let prefix = run(directory, &["rev-parse", "--show-prefix"], None)?;
+        let top_level = PathBuf::from(top_level.trim_end_matches('\n'));
+
+        let entries = run(&top_level, &["ls-files", "-s", "-z"], None)?
+            .split_terminator('\0')
+            .map(IndexEntry::parse)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(GitError::InvalidOutput("ls-files"))?;
+
+        // Without commits, everything in the index is uncommitted
+        let changed_paths = match run(&top_level, &["rev-parse", "--verify", "-q", "HEAD"], None) {
+            Ok(_) => run(&top_level, &["diff", "--name-only", "-z", "HEAD"], None)?
+                .split_terminator('\0')
+                .map(String::from)
+                .collect(),
+            Err(_) => entries.iter().map(|entry| entry.path.clone()).collect(),
+        };
+
+        Ok(Self {
+            top_level,
+            prefix: PathBuf::from(prefix.trim_end_matches('\n')),
+            entries,
+            changed_paths,
+        })
+    }
+
+    /// Converts a path relative to the directory into one relative to the top level.
+    /// Returns `None` for paths outside of the repository.
+    fn repository_path(&self, path: &Path) -> Option<String> {
+        let path = self.prefix.join(path);
+        let mut components: Vec<_> = Vec::new();
+
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => components.push(name.to_str()?),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    components.pop()?;
+                }
+                Component::RootDir | Component::Prefix(_) => return None,
+            }
+        }
+
+        Some(components.join("/"))
+    }
+
+    fn entries_within<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a IndexEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.is_within(path))
+    }
+
+    /// Returns why the rename would not be performed like `git mv` does, if at all.
+    pub(crate) fn check(&self, rename: &Rename, force: bool) -> Result<(), String> {
+        let source = self
+            .repository_path(&rename.source)
+            .ok_or_else(|| String::from("It is outside of the repository"))?;
+
+        let mut entries = self.entries_within(&source).peekable();
+        if entries.peek().is_none() {
+            // Untracked files are renamed like outside of repositories
+            return Ok(());
+        }
+
+        if self.repository_path(&rename.target).is_none() {
+            return Err(String::from("The target is outside of the repository"));
+        }
+
+        let has_changes =
+            entries.any(|entry| entry.stage != "0" || self.changed_paths.contains(&entry.path));
+        if has_changes && !force {
+            return Err(String::from(
+                "It has uncommitted changes, use --force to rename it anyway",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Moves the index entries of renamed files like `git mv`, keeping their staged contents.
+    pub(crate) fn record_renames(&self, renames: &[Rename]) -> Result<(), GitError> {
+        let index_info = self.index_info(renames);
+        if index_info.is_empty() {
+            return Ok(());
+        }
+
+        run(
+            &self.top_level,
+            &["update-index", "-z", "--index-info"],
+            Some(index_info.as_bytes()),
+        )
+        .map(|_| ())
+    }
+
+    /// Returns the input of `git update-index -z --index-info` that moves the entries.
+    fn index_info(&self, renames: &[Rename]) -> String {
+        // Removals come first, so that files in cycles do not remove each other's new entries
+        let mut removals = String::new();
+        let mut additions = String::new();
+
+        for rename in renames {
+            let (source, target) = match (
+                self.repository_path(&rename.source),
+                self.repository_path(&rename.target),
+            ) {
+                (Some(source), Some(target)) => (source, target),
+                _ => continue,
+            };
+
+            for entry in self.entries_within(&source) {
+                let path = format!("{}{}", target, &entry.path[source.len()..]);
+                let removed_object = "0".repeat(entry.object.len());
+
+                removals.push_str(&format!("0 {}\t{}\0", removed_object, entry.path));
+                additions.push_str(&format!(
+                    "{} {} {}\t{}\0",
+                    entry.mode, entry.object, entry.stage, path
+                ));
+            }
+        }
+
+        removals + &additions
+    }
+}
+
+fn run(
+    directory: &Path,
+    arguments: &[&'static str],
+    input: Option<&[u8]>,
+) -> Result<String, GitError> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(arguments)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(GitError::IoError)?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input).map_err(GitError::IoError)?;
+    }
+
+    let output = child.wait_with_output().map_err(GitError::IoError)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: arguments.join(" "),
+            message: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| GitError::InvalidOutput(arguments[0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository(prefix: &str, entries: &[&str], changed_paths: &[&str]) -> Repository {
+        Repository {
+            top_level: PathBuf::from("/repository"),
+            prefix: PathBuf::from(prefix),
+            entries: entries
+                .iter()
+                .map(|path| IndexEntry {
+                    mode: String::from("100644"),
+                    object: String::from("0123456789abcdef0123456789abcdef01234567"),
+                    stage: String::from("0"),
+                    path: String::from(*path),
+                })
+                .collect(),
+            changed_paths: changed_paths
+                .iter()
+                .map(|path| String::from(*path))
+                .collect(),
+        }
+    }
+
+    fn rename(source: &str, target: &str) -> Rename {
+        Rename {
+            source: PathBuf::from(source),
+            target: PathBuf::from(target),
+        }
+    }
+
+    #[test]
+    fn parses_index_entries() {
+        assert_eq!(
+            Some(IndexEntry {
+                mode: String::from("100644"),
+                object: String::from("abc"),
+                stage: String::from("0"),
+                path: String::from("dir/a b.txt"),
+            }),
+            IndexEntry::parse("100644 abc 0\tdir/a b.txt")
+        );
+        assert_eq!(None, IndexEntry::parse("100644 abc 0"));
+    }
+
+    #[test]
+    fn converts_paths_relative_to_the_top_level() {
+        let repository = repository("src/", &[], &[]);
+
+        assert_eq!(
+            Some(String::from("src/a.rs")),
+            repository.repository_path(Path::new("a.rs"))
+        );
+        assert_eq!(
+            Some(String::from("lib/a.rs")),
+            repository.repository_path(Path::new("./../lib/a.rs"))
+        );
+        assert_eq!(None, repository.repository_path(Path::new("../../a.rs")));
+    }
+
+    #[test]
+    fn refuses_files_with_uncommitted_changes() {
+        let repository = repository("", &["a", "dir/b", "dir/c"], &["dir/c"]);
+
+        assert!(repository.check(&rename("a", "x"), false).is_ok());
+        assert!(repository.check(&rename("untracked", "x"), false).is_ok());
+        assert!(repository.check(&rename("dir", "x"), false).is_err());
+        assert!(repository.check(&rename("dir", "x"), true).is_ok());
+        assert!(repository.check(&rename("a", "../x"), true).is_err());
+        assert!(repository.check(&rename("di", "x"), false).is_ok());
+    }
+
+    fn removal(path: &str) -> String {
+        format!("0 {}\t{}\0", "0".repeat(40), path)
+    }
+
+    fn addition(path: &str) -> String {
+        format!(
+            "100644 0123456789abcdef0123456789abcdef01234567 0\t{}\0",
+            path
+        )
+    }
+
+    #[test]
+    fn removes_entries_before_adding_them() {
+        let repository = repository("", &["a", "b"], &[]);
+
+        assert_eq!(
+            [removal("a"), removal("b"), addition("x"), addition("y")].concat(),
+            repository.index_info(&[rename("a", "x"), rename("b", "y"), rename("new", "z")])
+        );
+    }
+
+    #[test]
+    fn moves_entries_with_their_directory() {
+        let repository = repository("src/", &["src/dir/a", "src/dir/b/c", "src/directory"], &[]);
+
+        assert_eq!(
+            [
+                removal("src/dir/a"),
+                removal("src/dir/b/c"),
+                addition("lib/a"),
+                addition("lib/b/c"),
+            ]
+            .concat(),
+            repository.index_info(&[rename("dir", "../lib")])
+        );
+    }
+
+    #[test]
+    fn swaps_entries_in_cycles() {
+        let repository = repository("", &["a", "b"], &[]);
+
+        assert_eq!(
+            [removal("a"), removal("b"), addition("b"), addition("a")].concat(),
+            repository.index_info(&[rename("a", "b"), rename("b", "a")])
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use chrono::NaiveDateTime;
+use exif::{Context, In, Reader, Tag, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum ExifTag {
+    DateTimeOriginal,
+    DateTimeDigitized,
+    DateTime,
+    Make,
+    Model,
+    LensMake,
+    LensModel,
+    /// The number of the image in a sequence, e.g. a burst
+    ImageNumber,
+    /// The fraction of a second at which the image was taken, useful to tell bursts apart
+    SubSecTimeOriginal,
+}
+
+const EXIF_TAGS: [ExifTag; 9] = [
+    ExifTag::DateTimeOriginal,
+    ExifTag::DateTimeDigitized,
+    ExifTag::DateTime,
+    ExifTag::Make,
+    ExifTag::Model,
+    ExifTag::LensMake,
+    ExifTag::LensModel,
+    ExifTag::ImageNumber,
+    ExifTag::SubSecTimeOriginal,
+];
+
+impl ExifTag {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        EXIF_TAGS.iter().copied().find(|tag| tag.name() == name)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ExifTag::DateTimeOriginal => "DateTimeOriginal",
+            ExifTag::DateTimeDigitized => "DateTimeDigitized",
+            ExifTag::DateTime => "DateTime",
+            ExifTag::Make => "Make",
+            ExifTag::Model => "Model",
+            ExifTag::LensMake => "LensMake",
+            ExifTag::LensModel => "LensModel",
+            ExifTag::ImageNumber => "ImageNumber",
+            ExifTag::SubSecTimeOriginal => "SubSecTimeOriginal",
+        }
+    }
+
+    /// Whether the tag holds a timestamp that can be formatted.
+    pub(crate) fn is_date(self) -> bool {
+        matches!(
+            self,
+            ExifTag::DateTimeOriginal | ExifTag::DateTimeDigitized | ExifTag::DateTime
+        )
+    }
+
+    fn tag(self) -> Tag {
+        match self {
+            ExifTag::DateTimeOriginal => Tag::DateTimeOriginal,
+            ExifTag::DateTimeDigitized => Tag::DateTimeDigitized,
+            ExifTag::DateTime => Tag::DateTime,
+            ExifTag::Make => Tag::Make,
+            ExifTag::Model => Tag::Model,
+            ExifTag::LensMake => Tag::LensMake,
+            ExifTag::LensModel => Tag::LensModel,
+            // Defined by TIFF/EP, which is not covered by the exif crate
+            ExifTag::ImageNumber => Tag(Context::Tiff, 0x9211),
+            ExifTag::SubSecTimeOriginal => Tag::SubSecTimeOriginal,
+        }
+    }
+}
+
+/// Reads the supported EXIF tags of a JPEG or TIFF file.
+/// Files without (valid) EXIF data yield no tags.
+pub(crate) fn read_exif_tags(path: &Path) -> HashMap<ExifTag, String> {
+    File::open(path)
+        .map(|file| read_exif_tags_from(BufReader::new(file)))
+        .unwrap_or_default()
+}
+
+fn read_exif_tags_from(mut reader: impl BufRead + Seek) -> HashMap<ExifTag, String> {
+    let exif = match Reader::new().read_from_container(&mut reader) {
+        Ok(exif) => exif,
+        Err(_) => return HashMap::new(),
+    };
+
+    EXIF_TAGS
+        .iter()
+        .filter_map(|tag| {
+            let field = exif.get_field(tag.tag(), In::PRIMARY)?;
+
+            let value = match &field.value {
+                Value::Ascii(values) => values
+                    .first()
+                    .map(|value| String::from_utf8_lossy(value).trim().to_string())?,
+                Value::Byte(_) | Value::Short(_) | Value::Long(_) => {
+                    field.value.get_uint(0)?.to_string()
+                }
+                value => value.display_as(field.tag).to_string(),
+            };
+
+            if value.is_empty() {
+                None
+            } else {
+                Some((*tag, value))
+            }
+        })
+        .collect()
+}
+
+/// Parses EXIF timestamps, which look like `2023:04:05 06:07:08`.
+pub(crate) fn parse_exif_date(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%Y:%m:%d %H:%M:%S").ok()
+}
+
+/// Reads the width and height from the header of an image, e.g. a PNG, JPEG or WebP file.
+pub(crate) fn image_dimensions(path: &Path) -> Option<(usize, usize)> {
+    imagesize::size(path)
+        .ok()
+        .map(|size| (size.width, size.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A JPEG that contains nothing but an EXIF segment with a model and a capture date.
+    fn jpeg_with_exif() -> Vec<u8> {
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(b"II\x2a\x00\x08\x00\x00\x00");
+        // IFD0: Model and a pointer to the Exif IFD at offset 38
+        tiff.extend_from_slice(&[0x02, 0x00]);
+        tiff.extend_from_slice(&[0x10, 0x01, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00]);
+        tiff.extend_from_slice(b"Cam\x00");
+        tiff.extend_from_slice(&[0x69, 0x87, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00]);
+        tiff.extend_from_slice(&[38, 0x00, 0x00, 0x00]);
+        tiff.extend_from_slice(&[0x00; 4]);
+        // Exif IFD: DateTimeOriginal stored at offset 56
+        tiff.extend_from_slice(&[0x01, 0x00]);
+        tiff.extend_from_slice(&[0x03, 0x90, 0x02, 0x00, 0x14, 0x00, 0x00, 0x00]);
+        tiff.extend_from_slice(&[56, 0x00, 0x00, 0x00]);
+        tiff.extend_from_slice(&[0x00; 4]);
+        tiff.extend_from_slice(b"2023:04:05 06:07:08\x00");
+
+        let segment_length = (2 + 6 + tiff.len()) as u16;
+
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe1];
+        jpeg.extend_from_slice(&segment_length.to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\x00\x00");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xff, 0xd9]);
+        jpeg
+    }
+
+    #[test]
+    fn reads_exif_tags() {
+        let tags = read_exif_tags_from(Cursor::new(jpeg_with_exif()));
+
+        assert_eq!(Some(&String::from("Cam")), tags.get(&ExifTag::Model));
+        assert_eq!(
+            Some(&String::from("2023:04:05 06:07:08")),
+            tags.get(&ExifTag::DateTimeOriginal)
+        );
+        assert_eq!(None, tags.get(&ExifTag::Make));
+    }
+
+    #[test]
+    fn reads_no_tags_without_exif() {
+        assert!(read_exif_tags_from(Cursor::new(b"plain text".to_vec())).is_empty());
+    }
+
+    #[test]
+    fn parses_exif_dates() {
+        assert_eq!(
+            "20230405_060708",
+            parse_exif_date("2023:04:05 06:07:08")
+                .unwrap()
+                .format("%Y%m%d_%H%M%S")
+                .to_string()
+        );
+        assert_eq!(None, parse_exif_date("0000:00:00 00:00:00"));
+    }
+}
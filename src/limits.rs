@@ -0,0 +1,291 @@
+use crate::name_generator::split_extension;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt;
+use std::fmt::Display;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_NAME_MAX: usize = 255;
+const DEFAULT_PATH_MAX: usize = 4096;
+
+/// The maximum lengths in bytes of a single path component and of a whole path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Limits {
+    pub(crate) name_max: usize,
+    pub(crate) path_max: usize,
+}
+
+impl Limits {
+    /// Queries the file system of the directory, or of its closest existing ancestor
+    /// if the directory is yet to be created.
+    pub(crate) fn of_directory(directory: &Path) -> Self {
+        let existing_directory = directory
+            .ancestors()
+            .find(|ancestor| ancestor.is_dir())
+            .unwrap_or_else(|| Path::new("/"));
+
+        Self {
+            name_max: pathconf(existing_directory, libc::_PC_NAME_MAX).unwrap_or(DEFAULT_NAME_MAX),
+            path_max: pathconf(existing_directory, libc::_PC_PATH_MAX).unwrap_or(DEFAULT_PATH_MAX),
+        }
+    }
+}
+
+fn pathconf(path: &Path, name: libc::c_int) -> Option<usize> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let value = unsafe { libc::pathconf(path.as_ptr(), name) };
+    usize::try_from(value).ok().filter(|value| *value > 0)
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum LimitError {
+    NameTooLong { name: String, limit: usize },
+    PathTooLong { length: usize, limit: usize },
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitError::NameTooLong { name, limit } => write!(
+                f,
+                "{:?} is {} bytes long, but at most {} are allowed",
+                name,
+                name.len(),
+                limit
+            ),
+            LimitError::PathTooLong { length, limit } => write!(
+                f,
+                "The path is {} bytes long, but at most {} are allowed",
+                length, limit
+            ),
+        }
+    }
+}
+
+impl Error for LimitError {}
+
+/// Checks new names against the limits of the file systems they are created in
+/// and keeps track of the names that have been handed out.
+#[derive(Debug)]
+pub(crate) struct LimitChecker {
+    directory: PathBuf,
+    truncate: bool,
+    limits: HashMap<PathBuf, Limits>,
+    targets: HashSet<PathBuf>,
+}
+
+impl LimitChecker {
+    pub(crate) fn new(directory: PathBuf, truncate: bool) -> Self {
+        Self {
+            directory,
+            truncate,
+            limits: HashMap::new(),
+            targets: HashSet::new(),
+        }
+    }
+
+    /// Returns the new name, shortened if it is too long and truncating is enabled.
+    pub(crate) fn check(&mut self, new_name: &str) -> Result<String, LimitError> {
+        let path = self.directory.join(new_name);
+        let parent = path.parent().unwrap_or(&path).to_path_buf();
+        let limits = *self
+            .limits
+            .entry(parent.clone())
+            .or_insert_with(|| Limits::of_directory(&parent));
+
+        let directory = &self.directory;
+        let targets = &self.targets;
+        let is_taken = |name: &str| {
+            let path = directory.join(name);
+            targets.contains(&path) || path.symlink_metadata().is_ok()
+        };
+
+        let new_name = fit_name(
+            new_name,
+            directory.as_os_str().len(),
+            limits,
+            self.truncate,
+            is_taken,
+        )?;
+        self.targets.insert(self.directory.join(&new_name));
+
+        Ok(new_name)
+    }
+}
+
+/// Fits a name, relative to a directory whose path is `base_length` bytes long, into the limits.
+/// Only the file name is truncated, keeping its extension. Truncated names that are taken
+/// get a counter like `~1` appended to their stem.
+fn fit_name(
+    name: &str,
+    base_length: usize,
+    limits: Limits,
+    truncate: bool,
+    is_taken: impl Fn(&str) -> bool,
+) -> Result<String, LimitError> {
+    let (parent, file_name) = match name.rfind('/') {
+        Some(index) => name.split_at(index + 1),
+        None => ("", name),
+    };
+
+    if let Some(component) = parent
+        .split('/')
+        .find(|component| component.len() > limits.name_max)
+    {
+        return Err(LimitError::NameTooLong {
+            name: String::from(component),
+            limit: limits.name_max,
+        });
+    }
+
+    // The separator and the terminating null byte count towards the path limit
+    let path_length = base_length + 1 + name.len();
+    let path_budget = limits
+        .path_max
+        .saturating_sub(base_length + 1 + parent.len() + 1);
+    let budget = limits.name_max.min(path_budget);
+
+    if file_name.len() <= budget {
+        return Ok(String::from(name));
+    }
+
+    let error = if file_name.len() > limits.name_max {
+        LimitError::NameTooLong {
+            name: String::from(file_name),
+            limit: limits.name_max,
+        }
+    } else {
+        LimitError::PathTooLong {
+            length: path_length,
+            limit: limits.path_max - 1,
+        }
+    };
+
+    if !truncate {
+        return Err(error);
+    }
+
+    let (stem, extension) = split_extension(file_name);
+    let extension = if extension.is_empty() {
+        String::new()
+    } else {
+        format!(".{}", extension)
+    };
+
+    let mut counter = 0;
+    loop {
+        let suffix = if counter == 0 {
+            String::new()
+        } else {
+            format!("~{}", counter)
+        };
+
+        let stem_budget = budget.saturating_sub(extension.len() + suffix.len());
+        let truncated_stem = truncate_at_char_boundary(stem, stem_budget);
+        if truncated_stem.is_empty() {
+            return Err(error);
+        }
+
+        let truncated_name = format!("{}{}{}{}", parent, truncated_stem, suffix, extension);
+        if !is_taken(&truncated_name) {
+            return Ok(truncated_name);
+        }
+
+        counter += 1;
+    }
+}
+
+fn truncate_at_char_boundary(text: &str, max_length: usize) -> &str {
+    let mut end = max_length.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: Limits = Limits {
+        name_max: 10,
+        path_max: 30,
+    };
+
+    #[test]
+    fn accepts_names_within_limits() {
+        assert_eq!(
+            Ok(String::from("dir/0123456789")),
+            fit_name("dir/0123456789", 5, LIMITS, false, |_| false)
+        );
+    }
+
+    #[test]
+    fn rejects_long_names() {
+        assert_eq!(
+            Err(LimitError::NameTooLong {
+                name: String::from("0123456789a"),
+                limit: 10
+            }),
+            fit_name("dir/0123456789a", 5, LIMITS, false, |_| false)
+        );
+        assert_eq!(
+            Err(LimitError::NameTooLong {
+                name: String::from("0123456789a"),
+                limit: 10
+            }),
+            fit_name("0123456789a/file", 5, LIMITS, true, |_| false)
+        );
+        assert_eq!(
+            Err(LimitError::PathTooLong {
+                length: 31,
+                limit: 29
+            }),
+            fit_name("0123456789/0123456789", 9, LIMITS, false, |_| false)
+        );
+    }
+
+    #[test]
+    fn truncates_stem_and_keeps_extension() {
+        assert_eq!(
+            Ok(String::from("dir/abc.tar.gz")),
+            fit_name("dir/abcdefgh.tar.gz", 5, LIMITS, true, |_| false)
+        );
+        assert_eq!(
+            Ok(String::from("0123456789/012345.a")),
+            fit_name("0123456789/0123456789.a", 9, LIMITS, true, |_| false)
+        );
+    }
+
+    #[test]
+    fn truncates_at_character_boundaries() {
+        assert_eq!(
+            Ok(String::from("ääää.txt")),
+            fit_name(
+                "äääää.txt",
+                0,
+                Limits {
+                    name_max: 13,
+                    ..LIMITS
+                },
+                true,
+                |_| false
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_truncated_names_unique() {
+        let taken = ["abcdef.txt", "abcd~1.txt"];
+
+        assert_eq!(
+            Ok(String::from("abcd~2.txt")),
+            fit_name("abcdefghij.txt", 0, LIMITS, true, |name| taken
+                .contains(&name))
+        );
+    }
+}
@@ -1,63 +1,501 @@
+use crate::confirmation::TerminalPrompter;
 use crate::controller::{Controller, ControllerImpl, RenamerFactory};
 use crate::matcher::MatcherImpl;
 use crate::name_generator::NameGeneratorImpl;
-use crate::renamer::RenamerImpl;
-use clap::{crate_version, App, Arg};
+use crate::plan::{Plan, PlanError};
+use crate::plan_editor::ExternalEditor;
+use crate::rename_map::{read_map, MapFormat};
+use crate::renamer::{
+    apply_plan, CandidateSource, Exclusion, MappingRenamer, Renamer, RenamerImpl, RenamerOptions,
+    Review,
+};
+use crate::sanitization::{Platform, Replacements, Sanitizer};
+use crate::script::ScriptFlavor;
+use crate::sorting::{SortKey, SortOrder};
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
 use std::env::current_dir;
 use std::error::Error;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 
+mod audio_tags;
+mod case_conversion;
+mod confirmation;
+mod console;
+mod content_hash;
 mod controller;
+mod executor;
+mod git;
+mod image_metadata;
+mod limits;
 mod matcher;
 mod matching_pattern;
+mod metadata_filter;
 mod name_generator;
+mod plan;
+mod plan_editor;
+mod rename_map;
 mod renamer;
 mod replacement_pattern;
+mod sanitization;
+mod script;
+mod sorting;
+mod transliteration;
+mod traversal;
 
 const OLD_PATTERN_PARAMETER_NAME: &str = "old pattern";
 const NEW_PATTERN_PARAMETER_NAME: &str = "new pattern";
+const EXCLUDE_PARAMETER_NAME: &str = "exclude";
+const WHERE_PARAMETER_NAME: &str = "where";
+const SORT_PARAMETER_NAME: &str = "sort";
+const REVERSE_PARAMETER_NAME: &str = "reverse";
+const SANITIZE_PARAMETER_NAME: &str = "sanitize";
+const SANITIZE_REPLACE_PARAMETER_NAME: &str = "sanitize-replace";
+const TRUNCATE_PARAMETER_NAME: &str = "truncate";
+const ALLOW_ESCAPE_PARAMETER_NAME: &str = "allow-escape";
+const DRY_RUN_PARAMETER_NAME: &str = "dry-run";
+const INTERACTIVE_PARAMETER_NAME: &str = "interactive";
+const EDIT_PARAMETER_NAME: &str = "edit";
+const FROM_MAP_PARAMETER_NAME: &str = "from-map";
+const FILES_PARAMETER_NAME: &str = "files";
+const STDIN_PARAMETER_NAME: &str = "stdin";
+const NULL_PARAMETER_NAME: &str = "null";
+const MAP_FORMAT_PARAMETER_NAME: &str = "map-format";
+const FORMAT_PARAMETER_NAME: &str = "format";
+const EMIT_SCRIPT_PARAMETER_NAME: &str = "emit-script";
+const GIT_PARAMETER_NAME: &str = "git";
+const FORCE_PARAMETER_NAME: &str = "force";
+const PLAN_PARAMETER_NAME: &str = "plan";
+
+const PLAN_COMMAND_NAME: &str = "plan";
+const APPLY_COMMAND_NAME: &str = "apply";
+
+/// Lists all files unchanged, so that they can be renamed in the editor.
+const EDIT_MATCHING_PATTERN: &str = "(*)";
+const EDIT_REPLACEMENT_PATTERN: &str = "$0";
 
 fn main() -> Result<(), Box<dyn Error>> {
     let matches = App::new("mvr")
         .author("Jeremy Stucki")
         .version(crate_version!())
         .about("Rename batches of files")
-        .arg(
-            Arg::with_name(OLD_PATTERN_PARAMETER_NAME)
-                .required(true)
-                .help(
-                    "Use braces to indicate groups\n\
-                     Use question marks to match a single character\n\
-                     Use asterisks to match any amount of characters",
+        .args(&rename_arguments())
+        .args(&git_arguments())
+        .subcommand(
+            SubCommand::with_name(PLAN_COMMAND_NAME)
+                .about(
+                    "Print the renames as JSON instead of renaming, to review and apply them later",
+                )
+                .args(&rename_arguments())
+                .arg(
+                    Arg::with_name(FORMAT_PARAMETER_NAME)
+                        .long(FORMAT_PARAMETER_NAME)
+                        .possible_values(&["json"])
+                        .default_value("json")
+                        .help("The format of the plan"),
                 ),
         )
-        .arg(
-            Arg::with_name(NEW_PATTERN_PARAMETER_NAME)
-                .required(true)
-                .help("Use $n to insert a matched group (0-based)"),
+        .subcommand(
+            SubCommand::with_name(APPLY_COMMAND_NAME)
+                .about("Rename the files as planned, unless any of them changed since planning")
+                .arg(
+                    Arg::with_name(PLAN_PARAMETER_NAME)
+                        .required(true)
+                        .help("The plan created with mvr plan (- reads stdin)"),
+                )
+                .arg(
+                    Arg::with_name(DRY_RUN_PARAMETER_NAME)
+                        .long(DRY_RUN_PARAMETER_NAME)
+                        .short("n")
+                        .help("Print what would be renamed without renaming anything"),
+                )
+                .arg(emit_script_argument())
+                .args(&git_arguments()),
         )
+        .setting(AppSettings::SubcommandsNegateReqs)
         .get_matches();
 
-    let renamer_factory: Box<RenamerFactory> = Box::new(|matching_pattern, replacement_pattern| {
-        let matching_pattern_glob = matching_pattern.as_glob();
-        let matcher = MatcherImpl::new(matching_pattern);
-        let name_generator = NameGeneratorImpl::new(replacement_pattern);
+    match matches.subcommand() {
+        (PLAN_COMMAND_NAME, Some(plan_matches)) => rename_files(plan_matches, true),
+        (APPLY_COMMAND_NAME, Some(apply_matches)) => apply_plan_file(apply_matches),
+        _ => rename_files(&matches, false),
+    }
+}
+
+fn rename_files(matches: &ArgMatches, plan_only: bool) -> Result<(), Box<dyn Error>> {
+    let sort_order = SortOrder {
+        key: match matches.value_of(SORT_PARAMETER_NAME) {
+            Some("mtime") => SortKey::ModificationTime,
+            Some("size") => SortKey::Size,
+            _ => SortKey::Name,
+        },
+        reverse: matches.is_present(REVERSE_PARAMETER_NAME),
+    };
 
-        Box::new(RenamerImpl::new(
-            matching_pattern_glob,
-            Box::new(matcher),
-            Box::new(name_generator),
-        ))
+    let replacements = if matches.is_present(SANITIZE_REPLACE_PARAMETER_NAME) {
+        let map = matches
+            .value_of(SANITIZE_REPLACE_PARAMETER_NAME)
+            .unwrap_or_default();
+        Some(Replacements::parse(map)?)
+    } else {
+        None
+    };
+    let sanitizer = matches.value_of(SANITIZE_PARAMETER_NAME).map(|platform| {
+        let platform = match platform {
+            "windows" => Platform::Windows,
+            "macos" => Platform::MacOs,
+            "portable" => Platform::Portable,
+            _ => Platform::Posix,
+        };
+        Sanitizer::new(platform, replacements)
     });
+    let sanitizer = sanitizer.transpose()?;
+
+    let options = RenamerOptions {
+        sort_order,
+        sanitizer,
+        truncate_long_names: matches.is_present(TRUNCATE_PARAMETER_NAME),
+        allow_escape: matches.is_present(ALLOW_ESCAPE_PARAMETER_NAME),
+        dry_run: matches.is_present(DRY_RUN_PARAMETER_NAME),
+        null_separated: matches.is_present(NULL_PARAMETER_NAME),
+        plan_only,
+        emit_script: script_flavor(matches),
+        git: matches.is_present(GIT_PARAMETER_NAME),
+        force: matches.is_present(FORCE_PARAMETER_NAME),
+        ..RenamerOptions::default()
+    };
+    let interactive = matches.is_present(INTERACTIVE_PARAMETER_NAME);
+    let edit = matches.is_present(EDIT_PARAMETER_NAME);
+    let directory = current_dir().expect("Cannot access directory");
+
+    if let Some(map_path) = matches.value_of(FROM_MAP_PARAMETER_NAME) {
+        let map_path = Path::new(map_path);
+        let format = match matches.value_of(MAP_FORMAT_PARAMETER_NAME) {
+            Some("tsv") => MapFormat::Tsv,
+            Some("csv") => MapFormat::Csv,
+            Some("json") => MapFormat::JsonLines,
+            Some("nul") => MapFormat::NulSeparated,
+            _ => MapFormat::of_path(map_path),
+        };
+        let mappings = if map_path == Path::new("-") {
+            read_map(io::stdin(), format)?
+        } else {
+            read_map(File::open(map_path)?, format)?
+        };
+
+        let renamer = MappingRenamer::new(mappings, options, review(edit, interactive));
+        return renamer.rename_files_in_directory(&directory);
+    }
+
+    let paths = if matches.is_present(STDIN_PARAMETER_NAME) {
+        Some(read_paths(io::stdin(), options.null_separated)?)
+    } else {
+        matches
+            .values_of_os(FILES_PARAMETER_NAME)
+            .map(|files| files.map(PathBuf::from).collect())
+    };
+
+    let renamer_factory: Box<RenamerFactory> = Box::new(
+        move |matching_pattern, replacement_pattern, exclusion_patterns, filter_expression| {
+            let candidate_source = match &paths {
+                Some(paths) => CandidateSource::Paths(paths.clone()),
+                None => CandidateSource::Glob(matching_pattern.as_glob()),
+            };
+            let matcher = MatcherImpl::new(matching_pattern);
+            let exclusions = exclusion_patterns
+                .into_iter()
+                .map(|pattern| {
+                    Exclusion::new(pattern.to_string(), Box::new(MatcherImpl::new(pattern)))
+                })
+                .collect();
+            let name_generator = NameGeneratorImpl::new(replacement_pattern);
+
+            Box::new(RenamerImpl::new(
+                candidate_source,
+                Box::new(matcher),
+                exclusions,
+                filter_expression,
+                Box::new(name_generator),
+                options.clone(),
+                review(edit, interactive),
+            ))
+        },
+    );
 
     let controller = ControllerImpl::new(
         Box::new(matching_pattern::ParserImpl::new()),
         Box::new(replacement_pattern::ParserImpl::new()),
+        Box::new(metadata_filter::ParserImpl::new()),
         renamer_factory,
     );
 
-    let matching_pattern = matches.value_of(OLD_PATTERN_PARAMETER_NAME).unwrap();
-    let replacement_pattern = matches.value_of(NEW_PATTERN_PARAMETER_NAME).unwrap();
-    let directory = current_dir().expect("Cannot access directory");
+    let matching_pattern = matches
+        .value_of(OLD_PATTERN_PARAMETER_NAME)
+        .unwrap_or(EDIT_MATCHING_PATTERN);
+    let replacement_pattern = matches
+        .value_of(NEW_PATTERN_PARAMETER_NAME)
+        .unwrap_or(EDIT_REPLACEMENT_PATTERN);
+    let exclusion_patterns: Vec<_> = matches
+        .values_of(EXCLUDE_PARAMETER_NAME)
+        .map(Iterator::collect)
+        .unwrap_or_default();
+
+    controller.rename_files_by_pattern(
+        matching_pattern,
+        replacement_pattern,
+        &exclusion_patterns,
+        matches.value_of(WHERE_PARAMETER_NAME),
+        &directory,
+    )
+}
+
+/// The arguments for renaming, shared by the main command and `plan`.
+fn rename_arguments() -> Vec<Arg<'static, 'static>> {
+    vec![
+            Arg::with_name(OLD_PATTERN_PARAMETER_NAME)
+                .required_unless_one(&[EDIT_PARAMETER_NAME, FROM_MAP_PARAMETER_NAME])
+                .requires(NEW_PATTERN_PARAMETER_NAME)
+                .help(
+                    "Use braces to indicate groups\n\
+                     Use question marks to match a single character\n\
+                     Use asterisks to match any amount of characters",
+                ),
+            Arg::with_name(NEW_PATTERN_PARAMETER_NAME)
+                .required_unless_one(&[EDIT_PARAMETER_NAME, FROM_MAP_PARAMETER_NAME])
+                .help(
+                    "Use $n to insert a matched group (0-based)\n\
+                     Use ${n:filter} to transform a matched group, e.g. ${0:kebab}\n\
+                     Case filters: upper, lower, title, snake, kebab, camel, pascal\n\
+                     Number filters: 03 (zero pad to width), int (strip zeros)\n\
+                     Use ${1+100}, ${1-1} or ${1*2} for arithmetic\n\
+                     Text filters: 2:4 (offset and length, ' -3' counts from the end), trim, trunc(40)\n\
+                     Unicode filters: ascii (transliterate), slug (e.g. my-file-name), nfc, nfd\n\
+                     Use ${0/a/b} to replace the first and ${0//a/b} to replace all occurrences\n\
+                     Use ${1:date(%d%m%Y|%b-%d-%Y => %Y-%m-%d)} to reformat a date given in one of the formats\n\
+                     Use ${2:-text} for a default and ${2:+text} to only insert text if $2 is not empty\n\
+                     Use ${n:start=1,step=1,width=4} to number files (all settings are optional)\n\
+                     Add per=dir or per=$0 to restart numbering per directory or value\n\
+                     Variables: ${mtime:%Y-%m-%d}, ${ctime:%Y-%m-%d} (last status change, not creation),\n\
+                     ${size}, ${size:human}, ${stem}, ${ext}, ${parent}, ${dir:n} (n-th directory above the file)\n\
+                     Content hashes: ${sha256}, ${sha1}, ${blake3}, ${crc32}, e.g. ${sha256:12} for a prefix\n\
+                     Images: ${width}, ${height}, ${exif:DateTimeOriginal:%Y%m%d_%H%M%S}, ${exif:Model}\n\
+                     EXIF tags: DateTimeOriginal, DateTimeDigitized, DateTime, Make, Model,\n\
+                     LensMake, LensModel, ImageNumber, SubSecTimeOriginal\n\
+                     Add a fallback with :-, e.g. ${exif:DateTimeOriginal:%Y:-${mtime:%Y}}\n\
+                     Audio tags (MP3, FLAC, Ogg, MP4): ${tag:artist}, ${tag:albumartist}, ${tag:album},\n\
+                     ${tag:title}, ${tag:track}, ${tag:disc}, ${tag:year}, ${tag:genre} (slashes become _)",
+                ),
+            Arg::with_name(EXCLUDE_PARAMETER_NAME)
+                .long(EXCLUDE_PARAMETER_NAME)
+                .short("x")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("pattern")
+                .help("Skip files matching this pattern (same syntax as <old pattern>)"),
+            Arg::with_name(WHERE_PARAMETER_NAME)
+                .long(WHERE_PARAMETER_NAME)
+                .short("w")
+                .takes_value(true)
+                .value_name("expression")
+                .help(
+                    "Only rename files whose metadata satisfies the expression\n\
+                     e.g. 'type == file && size > 10M && mtime < 2024-01-01'\n\
+                     Properties: type (file, dir, symlink), size, mtime, age, owner, executable\n\
+                     Combine conditions with &&, || and !",
+                ),
+            Arg::with_name(SORT_PARAMETER_NAME)
+                .long(SORT_PARAMETER_NAME)
+                .short("s")
+                .takes_value(true)
+                .possible_values(&["name", "mtime", "size"])
+                .default_value("name")
+                .help("The order in which files are renamed and numbered"),
+            Arg::with_name(REVERSE_PARAMETER_NAME)
+                .long(REVERSE_PARAMETER_NAME)
+                .short("r")
+                .help("Reverse the sort order"),
+            Arg::with_name(SANITIZE_PARAMETER_NAME)
+                .long(SANITIZE_PARAMETER_NAME)
+                .takes_value(true)
+                .value_name("platform")
+                .possible_values(&["posix", "windows", "macos", "portable"])
+                .help(
+                    "Skip files whose new name is not valid on the platform\n\
+                     (e.g. because of characters like : or names like CON on windows)",
+                ),
+            Arg::with_name(SANITIZE_REPLACE_PARAMETER_NAME)
+                .long(SANITIZE_REPLACE_PARAMETER_NAME)
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .value_name("map")
+                .requires(SANITIZE_PARAMETER_NAME)
+                .help(
+                    "Rewrite invalid names instead of skipping them\n\
+                     Invalid characters become _ unless mapped otherwise, e.g. --sanitize-replace=':=-,?='\n\
+                     Trailing dots and spaces are removed and reserved names get a _ suffix",
+                ),
+            Arg::with_name(TRUNCATE_PARAMETER_NAME)
+                .long(TRUNCATE_PARAMETER_NAME)
+                .help(
+                    "Shorten names that are too long for the file system instead of skipping the files\n\
+                     The extension is kept and a counter like ~1 is added if the shortened name is taken",
+                ),
+            Arg::with_name(ALLOW_ESCAPE_PARAMETER_NAME)
+                .long(ALLOW_ESCAPE_PARAMETER_NAME)
+                .help(
+                    "Allow new names that lead out of the current directory\n\
+                     (e.g. through .., absolute paths or symlinked directories)",
+                ),
+            Arg::with_name(DRY_RUN_PARAMETER_NAME)
+                .long(DRY_RUN_PARAMETER_NAME)
+                .short("n")
+                .help("Print what would be renamed without renaming anything"),
+            emit_script_argument(),
+            Arg::with_name(INTERACTIVE_PARAMETER_NAME)
+                .long(INTERACTIVE_PARAMETER_NAME)
+                .short("i")
+                .help(
+                    "Ask before renaming each file\n\
+                     Answer yes, no, all (rename the remaining files without asking),\n\
+                     quit (skip the remaining files) or edit (enter a different name)",
+                ),
+            Arg::with_name(EDIT_PARAMETER_NAME)
+                .long(EDIT_PARAMETER_NAME)
+                .help(
+                    "Change the new names in $VISUAL or $EDITOR before renaming\n\
+                     Delete a line to skip its file. Without patterns, all files are listed",
+                ),
+            Arg::with_name(FILES_PARAMETER_NAME)
+                .multiple(true)
+                .help("Rename these files instead of the ones matching <old pattern> (after --)"),
+            Arg::with_name(STDIN_PARAMETER_NAME)
+                .long(STDIN_PARAMETER_NAME)
+                .conflicts_with(FILES_PARAMETER_NAME)
+                .help(
+                    "Rename the files listed on stdin instead of the ones matching <old pattern>\n\
+                     Files that do not match <old pattern> are skipped",
+                ),
+            Arg::with_name(NULL_PARAMETER_NAME)
+                .long(NULL_PARAMETER_NAME)
+                .short("0")
+                .help(
+                    "Read NUL separated file names with --stdin\n\
+                     and print the renames as NUL terminated pairs of old and new names",
+                ),
+            Arg::with_name(FROM_MAP_PARAMETER_NAME)
+                .long(FROM_MAP_PARAMETER_NAME)
+                .value_name("file")
+                .conflicts_with_all(&[
+                    OLD_PATTERN_PARAMETER_NAME,
+                    STDIN_PARAMETER_NAME,
+                    EXCLUDE_PARAMETER_NAME,
+                    WHERE_PARAMETER_NAME,
+                    SORT_PARAMETER_NAME,
+                    REVERSE_PARAMETER_NAME,
+                ])
+                .help(
+                    "Rename the files listed in the file instead of using patterns (- reads stdin)\n\
+                     The format depends on the extension unless given with --map-format",
+                ),
+            Arg::with_name(MAP_FORMAT_PARAMETER_NAME)
+                .long(MAP_FORMAT_PARAMETER_NAME)
+                .value_name("format")
+                .requires(FROM_MAP_PARAMETER_NAME)
+                .possible_values(&["tsv", "csv", "json", "nul"])
+                .help(
+                    "tsv: old<TAB>new per line (default), csv: old,new per line\n\
+                     json: [\"old\", \"new\"] or {\"from\": \"old\", \"to\": \"new\"} per line\n\
+                     nul: old and new names terminated by NUL bytes (default for stdin)",
+                ),
+    ]
+}
+
+fn apply_plan_file(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let mut input = String::new();
+    match matches.value_of(PLAN_PARAMETER_NAME) {
+        Some("-") | None => io::stdin().read_to_string(&mut input),
+        Some(path) => File::open(path).and_then(|mut file| file.read_to_string(&mut input)),
+    }
+    .map_err(PlanError::IoError)?;
+
+    let options = RenamerOptions {
+        dry_run: matches.is_present(DRY_RUN_PARAMETER_NAME),
+        emit_script: script_flavor(matches),
+        git: matches.is_present(GIT_PARAMETER_NAME),
+        force: matches.is_present(FORCE_PARAMETER_NAME),
+        ..RenamerOptions::default()
+    };
+
+    apply_plan(&Plan::from_json(&input)?, &options)
+}
+
+fn emit_script_argument() -> Arg<'static, 'static> {
+    Arg::with_name(EMIT_SCRIPT_PARAMETER_NAME)
+        .long(EMIT_SCRIPT_PARAMETER_NAME)
+        .value_name("flavor")
+        .possible_values(&["sh", "git"])
+        .help(
+            "Print a shell script that renames the files instead of renaming them\n\
+             sh uses mkdir -p and mv -n, git uses mkdir -p and git mv\n\
+             Both stop at the first target that already exists",
+        )
+}
+
+fn git_arguments() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name(GIT_PARAMETER_NAME)
+            .long(GIT_PARAMETER_NAME)
+            .conflicts_with(EMIT_SCRIPT_PARAMETER_NAME)
+            .help(
+                "Update the index of the enclosing git repository like git mv\n\
+                 Untracked files are renamed as usual, tracked files with uncommitted changes are skipped",
+            ),
+        Arg::with_name(FORCE_PARAMETER_NAME)
+            .long(FORCE_PARAMETER_NAME)
+            .requires(GIT_PARAMETER_NAME)
+            .help("Rename tracked files with uncommitted changes with --git"),
+    ]
+}
+
+fn script_flavor(matches: &ArgMatches) -> Option<ScriptFlavor> {
+    match matches.value_of(EMIT_SCRIPT_PARAMETER_NAME) {
+        Some("sh") => Some(ScriptFlavor::Shell),
+        Some("git") => Some(ScriptFlavor::Git),
+        _ => None,
+    }
+}
+
+/// Reads file names separated by line breaks or NUL bytes.
+fn read_paths(mut reader: impl Read, null_separated: bool) -> io::Result<Vec<PathBuf>> {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input)?;
+
+    let separator = if null_separated { b'\0' } else { b'\n' };
+
+    Ok(input
+        .split(|byte| *byte == separator)
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(OsStr::from_bytes(path)))
+        .collect())
+}
 
-    controller.rename_files_by_pattern(matching_pattern, replacement_pattern, &directory)
+fn review(edit: bool, interactive: bool) -> Review {
+    Review {
+        editor: if edit {
+            Some(Box::new(ExternalEditor))
+        } else {
+            None
+        },
+        prompter: if interactive {
+            Some(Box::new(TerminalPrompter::default()))
+        } else {
+            None
+        },
+    }
 }
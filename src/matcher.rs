@@ -57,7 +57,7 @@ impl Matcher for MatcherImpl {
             return Err(());
         }
 
-        let positions_and_lengths = lengths.iter().scan(0 as usize, |position, length| {
+        let positions_and_lengths = lengths.iter().scan(0usize, |position, length| {
             let own_position = *position;
             *position += *length;
             Some((own_position, *length))
@@ -95,7 +95,7 @@ fn consume_tokens<'a>(
         let length = consume_token(
             &input[current_position..],
             token,
-            &tokens.get(token_index + 1..).unwrap_or(&[]),
+            tokens.get(token_index + 1..).unwrap_or(&[]),
         )?;
 
         current_position += length;
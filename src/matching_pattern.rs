@@ -13,7 +13,6 @@ use std::num::NonZeroUsize;
 
 #[cfg(test)]
 use mockiato::mockable;
-use std::iter::repeat;
 
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) enum Token {
@@ -40,13 +39,7 @@ pub(crate) enum ParsingError {
 
 impl Pattern {
     pub fn as_glob(&self) -> String {
-        self.tokens()
-            .map(|element| match element {
-                Token::Text(text) => text.clone(),
-                Token::FixedLength(length) => repeat('?').take(length.get()).collect(),
-                Token::Wildcard => String::from("*"),
-            })
-            .collect()
+        self.tokens().map(Token::to_string).collect()
     }
 
     fn tokens(&self) -> impl Iterator<Item = &Token> {
@@ -57,6 +50,35 @@ impl Pattern {
     }
 }
 
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Text(text) => write!(f, "{}", text),
+            Token::FixedLength(length) => write!(f, "{}", "?".repeat(length.get())),
+            Token::Wildcard => write!(f, "*"),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for element in &self.elements {
+            match element {
+                Element::Token(token) => write!(f, "{}", token)?,
+                Element::Group(tokens) => {
+                    write!(f, "(")?;
+                    for token in tokens {
+                        write!(f, "{}", token)?;
+                    }
+                    write!(f, ")")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Display for ParsingError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let message = match self {
@@ -124,10 +146,7 @@ impl Parser for ParserImpl {
 fn contains_repeated_wildcards(pattern: &Pattern) -> bool {
     pattern
         .tokens()
-        .filter(|token| match token {
-            Token::FixedLength(_) => false,
-            _ => true,
-        })
+        .filter(|token| !matches!(token, Token::FixedLength(_)))
         .tuple_windows()
         .any(|(first_value, second_value)| {
             *first_value == Token::Wildcard && *second_value == Token::Wildcard
@@ -292,4 +311,18 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn displays_pattern_as_written() {
+        let pattern = ParserImpl::new().parse("foo_(??.*).bar").unwrap();
+
+        assert_eq!("foo_(??.*).bar", pattern.to_string());
+    }
+
+    #[test]
+    fn converts_pattern_to_glob() {
+        let pattern = ParserImpl::new().parse("foo_(??.*).bar").unwrap();
+
+        assert_eq!("foo_??.*.bar", pattern.as_glob());
+    }
 }
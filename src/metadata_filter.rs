@@ -0,0 +1,554 @@
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{char as nom_char, multispace0};
+use nom::combinator::{map, map_res, opt, value};
+use nom::multi::fold_many0;
+use nom::sequence::{delimited, preceded, tuple};
+use nom::IResult;
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt::{self, Debug, Display};
+use std::fs::Metadata;
+use std::time::{Duration, SystemTime};
+
+#[cfg(test)]
+use mockiato::mockable;
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub(crate) enum FileType {
+    File,
+    Directory,
+    Symlink,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Owner {
+    Id(u32),
+    Name(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Condition {
+    FileType(Operator, FileType),
+    Size(Operator, u64),
+    ModificationTime(Operator, SystemTime),
+    Age(Operator, Duration),
+    Owner(Operator, Owner),
+    Executable(Operator, bool),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Expression {
+    Condition(Condition),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+/// The parts of a file's metadata that conditions and new names can refer to.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FileProperties {
+    pub(crate) file_type: FileType,
+    pub(crate) size: u64,
+    pub(crate) modified: Option<SystemTime>,
+    /// The time of the last status change (`st_ctime`), which is not the creation time
+    pub(crate) changed: Option<SystemTime>,
+    pub(crate) owner: u32,
+    pub(crate) executable: bool,
+}
+
+impl From<&Metadata> for FileProperties {
+    fn from(metadata: &Metadata) -> Self {
+        use std::convert::TryFrom;
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let file_type = if metadata.file_type().is_symlink() {
+            FileType::Symlink
+        } else if metadata.is_dir() {
+            FileType::Directory
+        } else {
+            FileType::File
+        };
+
+        Self {
+            file_type,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            changed: u64::try_from(metadata.ctime())
+                .ok()
+                .zip(u32::try_from(metadata.ctime_nsec()).ok())
+                .map(|(seconds, nanoseconds)| {
+                    SystemTime::UNIX_EPOCH + Duration::new(seconds, nanoseconds)
+                }),
+            owner: metadata.uid(),
+            executable: metadata.permissions().mode() & 0o111 != 0,
+        }
+    }
+}
+
+impl Operator {
+    fn compare<T: PartialOrd>(self, actual: &T, expected: &T) -> bool {
+        match self {
+            Operator::Equal => actual == expected,
+            Operator::NotEqual => actual != expected,
+            Operator::Less => actual < expected,
+            Operator::LessOrEqual => actual <= expected,
+            Operator::Greater => actual > expected,
+            Operator::GreaterOrEqual => actual >= expected,
+        }
+    }
+
+    fn is_equality(self) -> bool {
+        self == Operator::Equal || self == Operator::NotEqual
+    }
+}
+
+impl Condition {
+    fn evaluate(&self, properties: &FileProperties, now: SystemTime) -> bool {
+        match self {
+            Condition::FileType(operator, file_type) => {
+                operator.compare(&properties.file_type, file_type)
+            }
+            Condition::Size(operator, size) => operator.compare(&properties.size, size),
+            Condition::ModificationTime(operator, time) => properties
+                .modified
+                .is_some_and(|modified| operator.compare(&modified, time)),
+            Condition::Age(operator, age) => properties
+                .modified
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|actual_age| operator.compare(&actual_age, age)),
+            Condition::Owner(operator, Owner::Id(id)) => operator.compare(&properties.owner, id),
+            Condition::Owner(operator, Owner::Name(name)) => {
+                let owner_name = user_name(properties.owner);
+                operator.compare(&owner_name.as_ref(), &Some(name))
+            }
+            Condition::Executable(operator, executable) => {
+                operator.compare(&properties.executable, executable)
+            }
+        }
+    }
+}
+
+impl Expression {
+    pub(crate) fn evaluate(&self, properties: &FileProperties, now: SystemTime) -> bool {
+        match self {
+            Expression::Condition(condition) => condition.evaluate(properties, now),
+            Expression::Not(expression) => !expression.evaluate(properties, now),
+            Expression::And(left, right) => {
+                left.evaluate(properties, now) && right.evaluate(properties, now)
+            }
+            Expression::Or(left, right) => {
+                left.evaluate(properties, now) || right.evaluate(properties, now)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ParsingError {
+    InvalidSyntax,
+}
+
+impl Display for ParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            ParsingError::InvalidSyntax => "The given filter expression is not valid",
+        };
+
+        write!(f, "{}", message)
+    }
+}
+
+impl Error for ParsingError {}
+
+#[cfg_attr(test, mockable)]
+pub(crate) trait Parser: Debug {
+    fn parse(&self, input: &str) -> Result<Expression, ParsingError>;
+}
+
+#[derive(Debug)]
+pub(crate) struct ParserImpl {}
+
+impl ParserImpl {
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Parser for ParserImpl {
+    fn parse(&self, input: &str) -> Result<Expression, ParsingError> {
+        match delimited(multispace0, disjunction, multispace0)(input)
+            .map_err(|_| ParsingError::InvalidSyntax)?
+        {
+            (remaining_text, _) if !remaining_text.is_empty() => Err(ParsingError::InvalidSyntax),
+            (_, expression) => Ok(expression),
+        }
+    }
+}
+
+fn disjunction(input: &str) -> IResult<&str, Expression> {
+    let (input, first) = conjunction(input)?;
+
+    fold_many0(
+        preceded(delimited(multispace0, tag("||"), multispace0), conjunction),
+        first,
+        |left, right| Expression::Or(Box::new(left), Box::new(right)),
+    )(input)
+}
+
+fn conjunction(input: &str) -> IResult<&str, Expression> {
+    let (input, first) = negation(input)?;
+
+    fold_many0(
+        preceded(delimited(multispace0, tag("&&"), multispace0), negation),
+        first,
+        |left, right| Expression::And(Box::new(left), Box::new(right)),
+    )(input)
+}
+
+fn negation(input: &str) -> IResult<&str, Expression> {
+    alt((
+        map(
+            preceded(tuple((nom_char('!'), multispace0)), negation),
+            |expression| Expression::Not(Box::new(expression)),
+        ),
+        delimited(
+            tuple((nom_char('('), multispace0)),
+            disjunction,
+            tuple((multispace0, nom_char(')'))),
+        ),
+        map(condition, Expression::Condition),
+    ))(input)
+}
+
+fn condition(input: &str) -> IResult<&str, Condition> {
+    let property = take_while1(|c: char| c.is_ascii_alphabetic());
+    let operator = alt((
+        value(Operator::Equal, tag("==")),
+        value(Operator::NotEqual, tag("!=")),
+        value(Operator::LessOrEqual, tag("<=")),
+        value(Operator::GreaterOrEqual, tag(">=")),
+        value(Operator::Less, tag("<")),
+        value(Operator::Greater, tag(">")),
+    ));
+    let operand = take_while1(|c: char| !(c.is_whitespace() || "()&|!=<>".contains(c)));
+
+    map_res(
+        tuple((
+            property,
+            opt(tuple((
+                delimited(multispace0, operator, multispace0),
+                operand,
+            ))),
+        )),
+        |(property, comparison)| match comparison {
+            Some((operator, operand)) => parse_condition(property, operator, operand),
+            None if property == "executable" => Ok(Condition::Executable(Operator::Equal, true)),
+            None => Err(()),
+        },
+    )(input)
+}
+
+fn parse_condition(property: &str, operator: Operator, operand: &str) -> Result<Condition, ()> {
+    match property {
+        "type" if operator.is_equality() => {
+            Ok(Condition::FileType(operator, parse_file_type(operand)?))
+        }
+        "size" => Ok(Condition::Size(operator, parse_size(operand)?)),
+        "mtime" => Ok(Condition::ModificationTime(operator, parse_time(operand)?)),
+        "age" => Ok(Condition::Age(operator, parse_duration(operand)?)),
+        "owner" if operator.is_equality() => Ok(Condition::Owner(operator, parse_owner(operand))),
+        "executable" if operator.is_equality() => Ok(Condition::Executable(
+            operator,
+            operand.parse().map_err(|_| ())?,
+        )),
+        _ => Err(()),
+    }
+}
+
+fn parse_file_type(input: &str) -> Result<FileType, ()> {
+    match input {
+        "file" | "f" => Ok(FileType::File),
+        "dir" | "directory" | "d" => Ok(FileType::Directory),
+        "symlink" | "link" | "l" => Ok(FileType::Symlink),
+        _ => Err(()),
+    }
+}
+
+/// Parses sizes such as `512`, `10K` or `1.5G`. Units are binary (`1K` = 1024 bytes).
+fn parse_size(input: &str) -> Result<u64, ()> {
+    let (number, unit) = split_unit(input);
+    let multiplier: u64 = match unit.trim_end_matches(['B', 'i']) {
+        "" => 1,
+        "k" | "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(()),
+    };
+
+    let number: f64 = number.parse().map_err(|_| ())?;
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parses durations such as `90s`, `12h` or `30d`.
+fn parse_duration(input: &str) -> Result<Duration, ()> {
+    let (number, unit) = split_unit(input);
+    let seconds: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(()),
+    };
+
+    let number: u64 = number.parse().map_err(|_| ())?;
+
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or(())
+}
+
+/// Parses local dates (`2024-01-01`) or date times (`2024-01-01T12:30:00`).
+fn parse_time(input: &str) -> Result<SystemTime, ()> {
+    let date_time = NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .map_err(|_| ())?;
+
+    Local
+        .from_local_datetime(&date_time)
+        .earliest()
+        .map(SystemTime::from)
+        .ok_or(())
+}
+
+fn parse_owner(input: &str) -> Owner {
+    input
+        .parse()
+        .map(Owner::Id)
+        .unwrap_or_else(|_| Owner::Name(String::from(input)))
+}
+
+/// Looks up the name of the user with the id, if there is one.
+fn user_name(uid: u32) -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 1024];
+
+    loop {
+        let mut passwd = std::mem::MaybeUninit::<libc::passwd>::uninit();
+        let mut result = std::ptr::null_mut();
+        let error = unsafe {
+            libc::getpwuid_r(
+                uid,
+                passwd.as_mut_ptr(),
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+
+        match error {
+            libc::ERANGE if buffer.len() < 1 << 20 => buffer.resize(buffer.len() * 2, 0),
+            0 if !result.is_null() => {
+                // The name points into the buffer, which is still alive here
+                let name = unsafe { CStr::from_ptr((*result).pw_name) };
+                return name.to_str().ok().map(String::from);
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn split_unit(input: &str) -> (&str, &str) {
+    let index = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+
+    input.split_at(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> FileProperties {
+        FileProperties {
+            file_type: FileType::File,
+            size: 2048,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1000)),
+            changed: None,
+            owner: 1000,
+            executable: false,
+        }
+    }
+
+    #[test]
+    fn parses_single_condition() {
+        let expected = Expression::Condition(Condition::FileType(Operator::Equal, FileType::File));
+
+        let actual = ParserImpl::new().parse("type == file").unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parses_sizes_with_units() {
+        let expected = Expression::Condition(Condition::Size(Operator::Greater, 10 * 1024 * 1024));
+
+        let actual = ParserImpl::new().parse("size>10M").unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn conjunction_binds_stronger_than_disjunction() {
+        let expected = Expression::Or(
+            Box::new(Expression::Condition(Condition::FileType(
+                Operator::Equal,
+                FileType::Directory,
+            ))),
+            Box::new(Expression::And(
+                Box::new(Expression::Condition(Condition::Executable(
+                    Operator::Equal,
+                    true,
+                ))),
+                Box::new(Expression::Not(Box::new(Expression::Condition(
+                    Condition::Age(Operator::Less, Duration::from_secs(2 * 24 * 60 * 60)),
+                )))),
+            )),
+        );
+
+        let actual = ParserImpl::new()
+            .parse("type == dir || executable && !age < 2d")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parses_parentheses() {
+        let expected = Expression::And(
+            Box::new(Expression::Or(
+                Box::new(Expression::Condition(Condition::Owner(
+                    Operator::Equal,
+                    Owner::Name(String::from("root")),
+                ))),
+                Box::new(Expression::Condition(Condition::Owner(
+                    Operator::Equal,
+                    Owner::Id(1000),
+                ))),
+            )),
+            Box::new(Expression::Condition(Condition::Size(
+                Operator::LessOrEqual,
+                512,
+            ))),
+        );
+
+        let actual = ParserImpl::new()
+            .parse("(owner == root || owner == 1000) && size <= 512")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn fails_with_ordering_on_file_type() {
+        let actual = ParserImpl::new().parse("type < file");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn fails_with_unknown_property() {
+        let actual = ParserImpl::new().parse("color == red");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn fails_with_out_of_range_duration() {
+        let actual = ParserImpl::new().parse("age > 99999999999999999w");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn fails_with_trailing_operator() {
+        let actual = ParserImpl::new().parse("type == file &&");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn evaluates_size_and_type() {
+        let expression = ParserImpl::new()
+            .parse("type == file && size > 1K && size < 1M")
+            .unwrap();
+
+        assert!(expression.evaluate(&properties(), SystemTime::now()));
+    }
+
+    #[test]
+    fn evaluates_age_relative_to_now() {
+        let expression = ParserImpl::new().parse("age > 10m").unwrap();
+        let modified = properties().modified.unwrap();
+
+        assert!(!expression.evaluate(&properties(), modified + Duration::from_secs(60)));
+        assert!(expression.evaluate(&properties(), modified + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn evaluates_modification_time() {
+        let expression = ParserImpl::new().parse("mtime < 2000-01-01").unwrap();
+
+        assert!(expression.evaluate(&properties(), SystemTime::now()));
+    }
+
+    #[test]
+    fn evaluates_owner_and_executable_bit() {
+        let expression = ParserImpl::new()
+            .parse("owner != 1000 || executable")
+            .unwrap();
+
+        assert!(!expression.evaluate(&properties(), SystemTime::now()));
+    }
+
+    #[test]
+    fn looks_up_user_names() {
+        assert_eq!(Some(String::from("root")), user_name(0));
+        assert_eq!(None, user_name(u32::MAX - 1));
+    }
+
+    #[test]
+    fn reads_status_change_time() {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = std::fs::symlink_metadata(std::env::temp_dir()).unwrap();
+        let changed = FileProperties::from(&metadata).changed.unwrap();
+
+        assert_eq!(
+            metadata.ctime() as u64,
+            changed
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        );
+    }
+}
@@ -1,12 +1,31 @@
+use crate::audio_tags::{read_audio_tags, AudioTag};
+use crate::case_conversion::convert_case;
+use crate::content_hash::{hash_file, HashAlgorithm};
+use crate::image_metadata::{image_dimensions, parse_exif_date, read_exif_tags, ExifTag};
 use crate::matcher::CaptureGroup;
-use crate::replacement_pattern::{Element, Pattern};
+use crate::metadata_filter::FileProperties;
+use crate::replacement_pattern::{Element, Filter, Pattern, Scope, Variable};
+use crate::transliteration::{slugify, to_ascii};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::env::current_dir;
 use std::error::Error;
 use std::fmt;
-use std::fmt::{Debug, Display};
+use std::fmt::{Debug, Display, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Debug, PartialEq)]
 pub(crate) enum NameGeneratorError {
     MissingCaptureGroup(usize),
+    NotNumeric(String),
+    NumericOverflow(String),
+    MissingVariable(&'static str),
+    UnreadableFile(String),
+    InvalidDate(String),
+    UnformattableDate(String),
 }
 
 impl Display for NameGeneratorError {
@@ -15,6 +34,23 @@ impl Display for NameGeneratorError {
             NameGeneratorError::MissingCaptureGroup(capture_group) => {
                 format!("Missing capture group: {:?}", capture_group)
             }
+            NameGeneratorError::NotNumeric(value) => format!("Not a number: {:?}", value),
+            NameGeneratorError::NumericOverflow(value) => {
+                format!("Number is out of range: {:?}", value)
+            }
+            NameGeneratorError::MissingVariable(variable) => {
+                format!("The {} is not available for this file", variable)
+            }
+            NameGeneratorError::InvalidDate(value) => {
+                format!("Not a date in any of the given formats: {:?}", value)
+            }
+            NameGeneratorError::UnformattableDate(format) => format!(
+                "The date has no time zone, which the format needs: {:?}",
+                format
+            ),
+            NameGeneratorError::UnreadableFile(reason) => {
+                format!("Unable to read the file contents: {}", reason)
+            }
         };
 
         write!(f, "{}", message)
@@ -23,41 +59,475 @@ impl Display for NameGeneratorError {
 
 impl Error for NameGeneratorError {}
 
+/// A file for which a new name is generated.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Entry {
+    pub(crate) path: PathBuf,
+    pub(crate) capture_groups: Vec<CaptureGroup>,
+    pub(crate) properties: FileProperties,
+}
+
 pub(crate) trait NameGenerator: Debug {
-    fn generate_name(
-        &self,
-        capture_groups: Vec<CaptureGroup>,
-    ) -> Result<String, NameGeneratorError>;
+    /// Generates the new names for a batch of files in the order in which they will be renamed.
+    fn generate_names(&self, entries: &[Entry]) -> Vec<Result<String, NameGeneratorError>>;
 }
 
 #[derive(Debug)]
 pub(crate) struct NameGeneratorImpl {
     replacement_pattern: Pattern,
+    content_hashes: RefCell<HashMap<(PathBuf, HashAlgorithm), String>>,
+    exif_tags: RefCell<HashMap<PathBuf, HashMap<ExifTag, String>>>,
+    image_dimensions: RefCell<HashMap<PathBuf, Option<(usize, usize)>>>,
+    audio_tags: RefCell<HashMap<PathBuf, HashMap<AudioTag, String>>>,
 }
 
+/// Counts how many files have been named so far for each counter scope.
+type Counts = HashMap<(Scope, String), usize>;
+
 impl NameGeneratorImpl {
     pub(crate) fn new(replacement_pattern: Pattern) -> Self {
         Self {
             replacement_pattern,
+            content_hashes: RefCell::new(HashMap::new()),
+            exif_tags: RefCell::new(HashMap::new()),
+            image_dimensions: RefCell::new(HashMap::new()),
+            audio_tags: RefCell::new(HashMap::new()),
         }
     }
-}
 
-impl NameGenerator for NameGeneratorImpl {
     fn generate_name(
         &self,
-        capture_groups: Vec<CaptureGroup>,
+        entry: &Entry,
+        counts: &mut Counts,
+    ) -> Result<String, NameGeneratorError> {
+        let mut used_scopes = HashSet::new();
+
+        let name = self.generate_elements(
+            &self.replacement_pattern.elements,
+            entry,
+            counts,
+            &mut used_scopes,
+        )?;
+
+        for scope in used_scopes {
+            *counts.entry(scope).or_insert(0) += 1;
+        }
+
+        Ok(name)
+    }
+
+    fn generate_elements(
+        &self,
+        elements: &[Element],
+        entry: &Entry,
+        counts: &Counts,
+        used_scopes: &mut HashSet<(Scope, String)>,
     ) -> Result<String, NameGeneratorError> {
-        self.replacement_pattern
-            .elements
+        elements
             .iter()
-            .map(|token| match token {
-                Element::Text(text) => Ok(text.clone()),
-                Element::CaptureGroup(index) => capture_groups
-                    .get(*index)
-                    .map(|capture_group| capture_group.contents.clone())
-                    .ok_or_else(|| NameGeneratorError::MissingCaptureGroup(*index)),
+            .map(|element| self.generate_element(element, entry, counts, used_scopes))
+            .collect()
+    }
+
+    fn generate_element(
+        &self,
+        element: &Element,
+        entry: &Entry,
+        counts: &Counts,
+        used_scopes: &mut HashSet<(Scope, String)>,
+    ) -> Result<String, NameGeneratorError> {
+        match element {
+            Element::Text(text) => Ok(text.clone()),
+            Element::CaptureGroup(index) => entry
+                .capture_groups
+                .get(*index)
+                .map(|capture_group| capture_group.contents.clone())
+                .ok_or(NameGeneratorError::MissingCaptureGroup(*index)),
+            Element::Counter(counter) => {
+                let scope = (
+                    counter.scope.clone(),
+                    self.scope_key(&counter.scope, entry, counts, used_scopes)?,
+                );
+                let count = counts.get(&scope).copied().unwrap_or(0);
+                used_scopes.insert(scope);
+
+                counter
+                    .step
+                    .checked_mul(count as i64)
+                    .and_then(|offset| counter.start.checked_add(offset))
+                    .map(|number| format_number(number, counter.width))
+                    .ok_or_else(|| {
+                        NameGeneratorError::NumericOverflow(format!(
+                            "{} + {} * {}",
+                            counter.start, counter.step, count
+                        ))
+                    })
+            }
+            Element::Variable(variable) => self.generate_variable(variable, entry),
+            Element::Filtered(element, filters) => {
+                let mut value = self.generate_element(element, entry, counts, used_scopes);
+
+                for filter in filters {
+                    value = match (filter, value) {
+                        (Filter::Default(elements), value) if is_missing(&value) => {
+                            self.generate_elements(elements, entry, counts, used_scopes)
+                        }
+                        (Filter::Conditional(_), value) if is_missing(&value) => Ok(String::new()),
+                        (Filter::Conditional(elements), Ok(_)) => {
+                            self.generate_elements(elements, entry, counts, used_scopes)
+                        }
+                        (filter, value) => value.and_then(|value| apply_filter(filter, value)),
+                    };
+                }
+
+                value
+            }
+        }
+    }
+
+    fn scope_key(
+        &self,
+        scope: &Scope,
+        entry: &Entry,
+        counts: &Counts,
+        used_scopes: &mut HashSet<(Scope, String)>,
+    ) -> Result<String, NameGeneratorError> {
+        match scope {
+            Scope::Global => Ok(String::new()),
+            Scope::Directory => Ok(entry
+                .path
+                .parent()
+                .map(|parent| parent.to_string_lossy().into_owned())
+                .unwrap_or_default()),
+            Scope::Value(element) => self.generate_element(element, entry, counts, used_scopes),
+        }
+    }
+
+    fn generate_variable(
+        &self,
+        variable: &Variable,
+        entry: &Entry,
+    ) -> Result<String, NameGeneratorError> {
+        let properties = &entry.properties;
+        let file_name = entry
+            .path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy())
+            .unwrap_or_default();
+
+        match variable {
+            Variable::ModificationTime(format) => properties
+                .modified
+                .map(|time| format_time(time, format))
+                .ok_or(NameGeneratorError::MissingVariable("modification time")),
+            Variable::ChangeTime(format) => properties
+                .changed
+                .map(|time| format_time(time, format))
+                .ok_or(NameGeneratorError::MissingVariable("status change time")),
+            Variable::Size {
+                human_readable: false,
+            } => Ok(properties.size.to_string()),
+            Variable::Size {
+                human_readable: true,
+            } => Ok(format_human_readable_size(properties.size)),
+            Variable::Stem => Ok(String::from(split_extension(&file_name).0)),
+            Variable::Extension => Ok(String::from(split_extension(&file_name).1)),
+            Variable::Directory(level) => directory_name(&entry.path, *level)
+                .ok_or(NameGeneratorError::MissingVariable("directory")),
+            Variable::Exif { tag, format } => {
+                let value = self
+                    .exif_tag(&entry.path, *tag)
+                    .ok_or(NameGeneratorError::MissingVariable("EXIF tag"))?;
+
+                match format {
+                    Some(format) => parse_exif_date(&value)
+                        .ok_or(NameGeneratorError::MissingVariable("EXIF date"))
+                        .and_then(|date| format_naive_date(date, format)),
+                    None => Ok(value),
+                }
+            }
+            Variable::AudioTag(tag) => self
+                .audio_tag(&entry.path, *tag)
+                .ok_or(NameGeneratorError::MissingVariable("audio tag")),
+            Variable::Width => self
+                .image_dimensions(&entry.path)
+                .map(|(width, _)| width.to_string())
+                .ok_or(NameGeneratorError::MissingVariable("image size")),
+            Variable::Height => self
+                .image_dimensions(&entry.path)
+                .map(|(_, height)| height.to_string())
+                .ok_or(NameGeneratorError::MissingVariable("image size")),
+            Variable::ContentHash { algorithm, length } => {
+                let hash = self.content_hash(&entry.path, *algorithm)?;
+                Ok(match length {
+                    Some(length) => hash.chars().take(*length).collect(),
+                    None => hash,
+                })
+            }
+        }
+    }
+
+    fn exif_tag(&self, path: &Path, tag: ExifTag) -> Option<String> {
+        self.exif_tags
+            .borrow_mut()
+            .entry(path.to_path_buf())
+            .or_insert_with(|| read_exif_tags(path))
+            .get(&tag)
+            .cloned()
+    }
+
+    fn audio_tag(&self, path: &Path, tag: AudioTag) -> Option<String> {
+        self.audio_tags
+            .borrow_mut()
+            .entry(path.to_path_buf())
+            .or_insert_with(|| read_audio_tags(path))
+            .get(&tag)
+            .cloned()
+    }
+
+    fn image_dimensions(&self, path: &Path) -> Option<(usize, usize)> {
+        *self
+            .image_dimensions
+            .borrow_mut()
+            .entry(path.to_path_buf())
+            .or_insert_with(|| image_dimensions(path))
+    }
+
+    /// Hashes each file at most once per algorithm, no matter how often the pattern uses it.
+    fn content_hash(
+        &self,
+        path: &Path,
+        algorithm: HashAlgorithm,
+    ) -> Result<String, NameGeneratorError> {
+        let key = (path.to_path_buf(), algorithm);
+
+        if let Some(hash) = self.content_hashes.borrow().get(&key) {
+            return Ok(hash.clone());
+        }
+
+        let hash = hash_file(path, algorithm).map_err(|error| {
+            NameGeneratorError::UnreadableFile(format!("{}: {}", path.display(), error))
+        })?;
+        self.content_hashes.borrow_mut().insert(key, hash.clone());
+
+        Ok(hash)
+    }
+}
+
+/// Whether the value is empty or refers to something that does not exist.
+fn is_missing(value: &Result<String, NameGeneratorError>) -> bool {
+    match value {
+        Ok(value) => value.is_empty(),
+        Err(NameGeneratorError::MissingCaptureGroup(_))
+        | Err(NameGeneratorError::MissingVariable(_)) => true,
+        Err(_) => false,
+    }
+}
+
+fn format_time(time: SystemTime, format: &str) -> String {
+    DateTime::<Local>::from(time).format(format).to_string()
+}
+
+/// Dates without a time zone fail to format items like `%z` instead of panicking.
+fn format_naive_date(date: NaiveDateTime, format: &str) -> Result<String, NameGeneratorError> {
+    let mut text = String::new();
+    write!(text, "{}", date.format(format))
+        .map_err(|_| NameGeneratorError::UnformattableDate(String::from(format)))?;
+
+    Ok(text)
+}
+
+/// Formats sizes like `ls -h`, e.g. `512`, `1.5K` or `12M`.
+fn format_human_readable_size(size: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+
+    if size < 1024 {
+        return size.to_string();
+    }
+
+    let mut value = size as f64;
+    let mut unit = UNITS[0];
+
+    for current_unit in UNITS.iter() {
+        value /= 1024.0;
+        unit = current_unit;
+
+        if value < 1024.0 {
+            break;
+        }
+    }
+
+    if value < 10.0 {
+        format!("{:.1}{}", value, unit)
+    } else {
+        format!("{:.0}{}", value, unit)
+    }
+}
+
+/// Extensions that are usually combined with `.tar`
+const COMPRESSION_EXTENSIONS: [&str; 9] =
+    ["gz", "bz2", "xz", "zst", "lz", "lzma", "lz4", "Z", "br"];
+
+/// Splits a file name into its stem and its extension, keeping compound extensions such as
+/// `tar.gz` together. Leading dots (as in `.bashrc`) are not treated as extensions.
+pub(crate) fn split_extension(file_name: &str) -> (&str, &str) {
+    match split_last_extension(file_name) {
+        Some((stem, extension)) if COMPRESSION_EXTENSIONS.contains(&extension) => {
+            match split_last_extension(stem) {
+                Some((inner_stem, "tar")) => (inner_stem, &file_name[inner_stem.len() + 1..]),
+                _ => (stem, extension),
+            }
+        }
+        Some((stem, extension)) => (stem, extension),
+        None => (file_name, ""),
+    }
+}
+
+fn split_last_extension(name: &str) -> Option<(&str, &str)> {
+    match name.rfind('.') {
+        Some(index) if index > 0 => Some((&name[..index], &name[index + 1..])),
+        _ => None,
+    }
+}
+
+/// Returns the name of the n-th directory above the path. Relative paths are resolved
+/// against the working directory once they run out of components.
+fn directory_name(path: &Path, level: usize) -> Option<String> {
+    let file_name = |ancestor: &Path| {
+        ancestor
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+    };
+
+    match path.ancestors().nth(level) {
+        Some(ancestor) if ancestor.file_name().is_some() => file_name(ancestor),
+        _ if path.is_relative() => {
+            file_name(current_dir().ok()?.join(path).ancestors().nth(level)?)
+        }
+        _ => None,
+    }
+}
+
+fn apply_filter(filter: &Filter, value: String) -> Result<String, NameGeneratorError> {
+    match filter {
+        // These need the other elements of the entry and are handled by `generate_element`
+        Filter::Default(_) | Filter::Conditional(_) => Ok(value),
+        Filter::Case(case) => Ok(convert_case(&value, *case)),
+        Filter::Pad(width) => Ok(format_number(parse_number(&value)?, *width)),
+        Filter::Integer => Ok(parse_number(&value)?.to_string()),
+        Filter::Add(operand) => apply_arithmetic(&value, |number| number.checked_add(*operand)),
+        Filter::Multiply(operand) => {
+            apply_arithmetic(&value, |number| number.checked_mul(*operand))
+        }
+        Filter::Slice { offset, length } => Ok(slice(&value, *offset, *length)),
+        Filter::Replace {
+            pattern,
+            replacement,
+            all: true,
+        } => Ok(value.replace(pattern, replacement)),
+        Filter::Replace {
+            pattern,
+            replacement,
+            all: false,
+        } => Ok(value.replacen(pattern, replacement, 1)),
+        Filter::Trim => Ok(String::from(value.trim())),
+        Filter::Truncate(length) => Ok(value.chars().take(*length).collect()),
+        Filter::Ascii => Ok(to_ascii(&value)),
+        Filter::Slug => Ok(slugify(&value)),
+        Filter::Nfc => Ok(value.nfc().collect()),
+        Filter::Nfd => Ok(value.nfd().collect()),
+        Filter::Date {
+            input_formats,
+            output_format,
+        } => match parse_date(&value, input_formats) {
+            Some(date) => format_naive_date(date, output_format),
+            None => Err(NameGeneratorError::InvalidDate(value)),
+        },
+    }
+}
+
+/// Formats without a time of day are read as dates at midnight.
+fn parse_date(value: &str, formats: &[String]) -> Option<NaiveDateTime> {
+    formats.iter().find_map(|format| {
+        NaiveDateTime::parse_from_str(value, format)
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(value, format)
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
             })
+    })
+}
+
+fn slice(value: &str, offset: i64, length: Option<i64>) -> String {
+    let char_count = value.chars().count() as i64;
+    let resolve = |position: i64| {
+        if position < 0 {
+            (char_count + position).max(0)
+        } else {
+            position.min(char_count)
+        }
+    };
+
+    let start = resolve(offset);
+    let end = match length {
+        Some(length) if length < 0 => resolve(length),
+        Some(length) => (start + length).min(char_count),
+        None => char_count,
+    };
+
+    value
+        .chars()
+        .skip(start as usize)
+        .take((end - start).max(0) as usize)
+        .collect()
+}
+
+fn parse_number(value: &str) -> Result<i64, NameGeneratorError> {
+    if !value.chars().any(|c| c.is_ascii_digit()) {
+        return Err(NameGeneratorError::NotNumeric(String::from(value)));
+    }
+
+    value.parse().map_err(|_| {
+        if value
+            .trim_start_matches(['+', '-'])
+            .chars()
+            .all(|c| c.is_ascii_digit())
+        {
+            NameGeneratorError::NumericOverflow(String::from(value))
+        } else {
+            NameGeneratorError::NotNumeric(String::from(value))
+        }
+    })
+}
+
+fn format_number(number: i64, width: usize) -> String {
+    format!("{:0width$}", number, width = width)
+}
+
+/// Applies the operation while keeping zero padding (`007` + 1 = `008`).
+fn apply_arithmetic(
+    value: &str,
+    operation: impl Fn(i64) -> Option<i64>,
+) -> Result<String, NameGeneratorError> {
+    let width = if value.len() > 1 && value.starts_with('0') {
+        value.len()
+    } else {
+        0
+    };
+
+    operation(parse_number(value)?)
+        .map(|number| format_number(number, width))
+        .ok_or_else(|| NameGeneratorError::NumericOverflow(String::from(value)))
+}
+
+impl NameGenerator for NameGeneratorImpl {
+    fn generate_names(&self, entries: &[Entry]) -> Vec<Result<String, NameGeneratorError>> {
+        let mut counts = Counts::new();
+
+        entries
+            .iter()
+            .map(|entry| self.generate_name(entry, &mut counts))
             .collect()
     }
 }
@@ -65,8 +535,48 @@ impl NameGenerator for NameGeneratorImpl {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::case_conversion::Case;
     use crate::matcher::CaptureGroup;
-    use crate::replacement_pattern::Element;
+    use crate::metadata_filter::FileType;
+    use crate::replacement_pattern::{Counter, Element};
+    use std::time::Duration;
+
+    fn properties() -> FileProperties {
+        FileProperties {
+            file_type: FileType::File,
+            size: 1536,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            changed: None,
+            owner: 1000,
+            executable: false,
+        }
+    }
+
+    fn entry(path: &str, capture_groups: &[&str]) -> Entry {
+        Entry {
+            path: PathBuf::from(path),
+            capture_groups: capture_groups
+                .iter()
+                .map(|contents| CaptureGroup {
+                    contents: String::from(*contents),
+                })
+                .collect(),
+            properties: properties(),
+        }
+    }
+
+    fn generate_name(
+        name_generator: &NameGeneratorImpl,
+        capture_groups: Vec<CaptureGroup>,
+    ) -> Result<String, NameGeneratorError> {
+        let entry = Entry {
+            path: PathBuf::from("file"),
+            capture_groups,
+            properties: properties(),
+        };
+
+        name_generator.generate_names(&[entry]).remove(0)
+    }
 
     #[test]
     fn works_with_text_only_pattern() {
@@ -75,7 +585,7 @@ mod tests {
         };
 
         let name_generator = NameGeneratorImpl::new(pattern);
-        let name = name_generator.generate_name(vec![]);
+        let name = generate_name(&name_generator, vec![]);
 
         assert_eq!("foo", name.unwrap());
     }
@@ -87,7 +597,7 @@ mod tests {
         };
 
         let name_generator = NameGeneratorImpl::new(pattern);
-        let name = name_generator.generate_name(vec![]);
+        let name = generate_name(&name_generator, vec![]);
 
         assert_eq!(
             NameGeneratorError::MissingCaptureGroup(2),
@@ -102,17 +612,20 @@ mod tests {
         };
 
         let name_generator = NameGeneratorImpl::new(pattern);
-        let name = name_generator.generate_name(vec![
-            CaptureGroup {
-                contents: String::from("foo"),
-            },
-            CaptureGroup {
-                contents: String::from("bar"),
-            },
-            CaptureGroup {
-                contents: String::from("baz"),
-            },
-        ]);
+        let name = generate_name(
+            &name_generator,
+            vec![
+                CaptureGroup {
+                    contents: String::from("foo"),
+                },
+                CaptureGroup {
+                    contents: String::from("bar"),
+                },
+                CaptureGroup {
+                    contents: String::from("baz"),
+                },
+            ],
+        );
 
         assert_eq!(String::from("barfoo"), name.unwrap());
     }
@@ -128,17 +641,20 @@ mod tests {
         };
 
         let name_generator = NameGeneratorImpl::new(pattern);
-        let name = name_generator.generate_name(vec![
-            CaptureGroup {
-                contents: String::from("foo"),
-            },
-            CaptureGroup {
-                contents: String::from("bar"),
-            },
-            CaptureGroup {
-                contents: String::from("baz"),
-            },
-        ]);
+        let name = generate_name(
+            &name_generator,
+            vec![
+                CaptureGroup {
+                    contents: String::from("foo"),
+                },
+                CaptureGroup {
+                    contents: String::from("bar"),
+                },
+                CaptureGroup {
+                    contents: String::from("baz"),
+                },
+            ],
+        );
 
         assert_eq!(String::from("foofoobar"), name.unwrap());
     }
@@ -150,10 +666,562 @@ mod tests {
         };
 
         let name_generator = NameGeneratorImpl::new(pattern);
-        let name = name_generator.generate_name(vec![CaptureGroup {
-            contents: String::from("foo"),
-        }]);
+        let name = generate_name(
+            &name_generator,
+            vec![CaptureGroup {
+                contents: String::from("foo"),
+            }],
+        );
 
         assert_eq!(String::from("foo"), name.unwrap());
     }
+
+    #[test]
+    fn applies_filters_to_capture_group() {
+        let pattern = Pattern {
+            elements: vec![
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(0)),
+                    vec![Filter::Case(Case::Kebab)],
+                ),
+                Element::Text(String::from(".")),
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(1)),
+                    vec![Filter::Case(Case::Lower)],
+                ),
+            ],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let name = generate_name(
+            &name_generator,
+            vec![
+                CaptureGroup {
+                    contents: String::from("My Holiday Photo"),
+                },
+                CaptureGroup {
+                    contents: String::from("JPG"),
+                },
+            ],
+        );
+
+        assert_eq!(String::from("my-holiday-photo.jpg"), name.unwrap());
+    }
+
+    #[test]
+    fn fails_with_missing_filtered_capture_group() {
+        let pattern = Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::CaptureGroup(1)),
+                vec![Filter::Case(Case::Upper)],
+            )],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let name = generate_name(&name_generator, vec![]);
+
+        assert_eq!(
+            NameGeneratorError::MissingCaptureGroup(1),
+            name.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn pads_and_strips_numbers() {
+        assert_eq!(
+            Ok(String::from("007")),
+            apply_filter(&Filter::Pad(3), String::from("7"))
+        );
+        assert_eq!(
+            Ok(String::from("1234")),
+            apply_filter(&Filter::Pad(3), String::from("1234"))
+        );
+        assert_eq!(
+            Ok(String::from("7")),
+            apply_filter(&Filter::Integer, String::from("007"))
+        );
+        assert_eq!(
+            Ok(String::from("0")),
+            apply_filter(&Filter::Integer, String::from("000"))
+        );
+    }
+
+    #[test]
+    fn arithmetic_preserves_padding() {
+        assert_eq!(
+            Ok(String::from("107")),
+            apply_filter(&Filter::Add(100), String::from("007"))
+        );
+        assert_eq!(
+            Ok(String::from("010")),
+            apply_filter(&Filter::Add(1), String::from("009"))
+        );
+        assert_eq!(
+            Ok(String::from("14")),
+            apply_filter(&Filter::Multiply(2), String::from("7"))
+        );
+        assert_eq!(
+            Ok(String::from("5")),
+            apply_filter(&Filter::Add(-5), String::from("10"))
+        );
+    }
+
+    #[test]
+    fn fails_with_non_numeric_capture_group() {
+        let pattern = Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::CaptureGroup(0)),
+                vec![Filter::Pad(3)],
+            )],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let name = generate_name(
+            &name_generator,
+            vec![CaptureGroup {
+                contents: String::from("seven"),
+            }],
+        );
+
+        assert_eq!(
+            NameGeneratorError::NotNumeric(String::from("seven")),
+            name.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn fails_with_overflow() {
+        assert_eq!(
+            Err(NameGeneratorError::NumericOverflow(String::from(
+                "99999999999999999999"
+            ))),
+            apply_filter(&Filter::Integer, String::from("99999999999999999999"))
+        );
+        assert_eq!(
+            Err(NameGeneratorError::NumericOverflow(String::from("1"))),
+            apply_filter(&Filter::Add(i64::MAX), String::from("1"))
+        );
+        assert_eq!(
+            Err(NameGeneratorError::NumericOverflow(String::from("2"))),
+            apply_filter(&Filter::Multiply(i64::MAX), String::from("2"))
+        );
+    }
+
+    #[test]
+    fn fails_with_counter_overflow() {
+        let pattern = Pattern {
+            elements: vec![Element::Counter(Counter {
+                step: i64::MAX,
+                ..Counter::default()
+            })],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[entry("a", &[]), entry("b", &[])]);
+
+        assert_eq!(Ok(String::from("1")), names[0]);
+        assert_eq!(
+            Err(NameGeneratorError::NumericOverflow(format!(
+                "1 + {} * 1",
+                i64::MAX
+            ))),
+            names[1]
+        );
+    }
+
+    #[test]
+    fn counts_generated_names() {
+        let pattern = Pattern {
+            elements: vec![
+                Element::CaptureGroup(0),
+                Element::Counter(Counter {
+                    start: 10,
+                    step: 5,
+                    width: 3,
+                    scope: Scope::Global,
+                }),
+            ],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[
+            entry("a", &["img"]),
+            entry("b", &[]),
+            entry("c", &["img"]),
+        ]);
+
+        assert_eq!(Ok(String::from("img010")), names[0]);
+        assert!(names[1].is_err());
+        assert_eq!(Ok(String::from("img015")), names[2]);
+    }
+
+    #[test]
+    fn restarts_counter_per_capture_group_value() {
+        let pattern = Pattern {
+            elements: vec![
+                Element::CaptureGroup(0),
+                Element::Text(String::from("_")),
+                Element::Counter(Counter {
+                    scope: Scope::Value(Box::new(Element::CaptureGroup(0))),
+                    width: 3,
+                    ..Counter::default()
+                }),
+            ],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[
+            entry("IMG_1.jpg", &["2024-01-01"]),
+            entry("IMG_2.jpg", &["2024-01-02"]),
+            entry("IMG_3.jpg", &["2024-01-01"]),
+        ]);
+
+        assert_eq!(
+            vec![
+                Ok(String::from("2024-01-01_001")),
+                Ok(String::from("2024-01-02_001")),
+                Ok(String::from("2024-01-01_002")),
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn restarts_counter_per_directory() {
+        let pattern = Pattern {
+            elements: vec![Element::Counter(Counter {
+                scope: Scope::Directory,
+                ..Counter::default()
+            })],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[
+            entry("a/1.jpg", &[]),
+            entry("b/1.jpg", &[]),
+            entry("a/2.jpg", &[]),
+            entry("3.jpg", &[]),
+        ]);
+
+        assert_eq!(
+            vec![
+                Ok(String::from("1")),
+                Ok(String::from("1")),
+                Ok(String::from("2")),
+                Ok(String::from("1")),
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn slices_by_characters() {
+        assert_eq!("llo", slice("héllo wörld", 2, Some(3)));
+        assert_eq!("wörld", slice("héllo wörld", 6, None));
+        assert_eq!("rld", slice("héllo wörld", -3, None));
+        assert_eq!("éllo wörl", slice("héllo wörld", 1, Some(-1)));
+        assert_eq!("", slice("abc", 5, Some(2)));
+        assert_eq!("abc", slice("abc", -10, None));
+    }
+
+    #[test]
+    fn replaces_first_or_all_occurrences() {
+        let replace = |all| Filter::Replace {
+            pattern: String::from(" "),
+            replacement: String::from("_"),
+            all,
+        };
+
+        assert_eq!(
+            Ok(String::from("a_b c")),
+            apply_filter(&replace(false), String::from("a b c"))
+        );
+        assert_eq!(
+            Ok(String::from("a_b_c")),
+            apply_filter(&replace(true), String::from("a b c"))
+        );
+    }
+
+    #[test]
+    fn trims_and_truncates() {
+        assert_eq!(
+            Ok(String::from("a title")),
+            apply_filter(&Filter::Trim, String::from("  a title \t"))
+        );
+        assert_eq!(
+            Ok(String::from("Ünïcö")),
+            apply_filter(&Filter::Truncate(5), String::from("Ünïcödé"))
+        );
+    }
+
+    #[test]
+    fn uses_default_for_empty_or_missing_values() {
+        let pattern = Pattern {
+            elements: vec![
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(0)),
+                    vec![Filter::Default(vec![Element::Text(String::from(
+                        "untitled",
+                    ))])],
+                ),
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(1)),
+                    vec![Filter::Default(vec![
+                        Element::Text(String::from("_")),
+                        Element::CaptureGroup(0),
+                    ])],
+                ),
+            ],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[
+            entry("a", &["foo", "bar"]),
+            entry("b", &["", "bar"]),
+            entry("c", &["foo"]),
+        ]);
+
+        assert_eq!(
+            vec![
+                Ok(String::from("foobar")),
+                Ok(String::from("untitledbar")),
+                Ok(String::from("foo_foo")),
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn emits_conditional_segment_only_for_non_empty_values() {
+        let pattern = Pattern {
+            elements: vec![
+                Element::CaptureGroup(0),
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(1)),
+                    vec![Filter::Conditional(vec![
+                        Element::Text(String::from("_")),
+                        Element::Filtered(
+                            Box::new(Element::CaptureGroup(1)),
+                            vec![Filter::Case(Case::Upper)],
+                        ),
+                    ])],
+                ),
+            ],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[
+            entry("a", &["foo", "bar"]),
+            entry("b", &["foo", ""]),
+            entry("c", &["foo"]),
+        ]);
+
+        assert_eq!(
+            vec![
+                Ok(String::from("foo_BAR")),
+                Ok(String::from("foo")),
+                Ok(String::from("foo")),
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn generates_metadata_variables() {
+        let pattern = Pattern {
+            elements: vec![
+                Element::Variable(Variable::ModificationTime(String::from("%Y"))),
+                Element::Text(String::from("_")),
+                Element::Variable(Variable::Size {
+                    human_readable: true,
+                }),
+                Element::Text(String::from("_")),
+                Element::Variable(Variable::Size {
+                    human_readable: false,
+                }),
+            ],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[entry("a.txt", &[])]);
+
+        assert_eq!(vec![Ok(String::from("2023_1.5K_1536"))], names);
+    }
+
+    #[test]
+    fn falls_back_if_variable_is_missing() {
+        let pattern = Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::Variable(Variable::ChangeTime(String::from("%Y")))),
+                vec![Filter::Default(vec![Element::Text(String::from(
+                    "unknown",
+                ))])],
+            )],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[entry("a.txt", &[])]);
+
+        assert_eq!(vec![Ok(String::from("unknown"))], names);
+    }
+
+    #[test]
+    fn generates_path_variables() {
+        let pattern = Pattern {
+            elements: vec![
+                Element::Variable(Variable::Directory(2)),
+                Element::Text(String::from("-")),
+                Element::Variable(Variable::Directory(1)),
+                Element::Text(String::from("-")),
+                Element::Variable(Variable::Stem),
+                Element::Text(String::from("-")),
+                Element::Variable(Variable::Extension),
+            ],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[entry("a/b/archive.tar.gz", &[])]);
+
+        assert_eq!(vec![Ok(String::from("a-b-archive-tar.gz"))], names);
+    }
+
+    #[test]
+    fn splits_extensions() {
+        assert_eq!(("photo", "jpg"), split_extension("photo.jpg"));
+        assert_eq!(("my.photo", "jpg"), split_extension("my.photo.jpg"));
+        assert_eq!(("backup", "tar.xz"), split_extension("backup.tar.xz"));
+        assert_eq!(("notes", "gz"), split_extension("notes.gz"));
+        assert_eq!((".bashrc", ""), split_extension(".bashrc"));
+        assert_eq!(("README", ""), split_extension("README"));
+    }
+
+    #[test]
+    fn formats_human_readable_sizes() {
+        assert_eq!("512", format_human_readable_size(512));
+        assert_eq!("1.0K", format_human_readable_size(1024));
+        assert_eq!("12M", format_human_readable_size(12 * 1024 * 1024 + 1));
+        assert_eq!("2.5G", format_human_readable_size(5 * 512 * 1024 * 1024));
+    }
+
+    #[test]
+    fn fails_with_exif_date_formats_that_need_a_time_zone() {
+        let exif_date = |format: &str| Pattern {
+            elements: vec![Element::Variable(Variable::Exif {
+                tag: ExifTag::DateTimeOriginal,
+                format: Some(String::from(format)),
+            })],
+        };
+        let generate = |format: &str| {
+            let name_generator = NameGeneratorImpl::new(exif_date(format));
+            name_generator.exif_tags.borrow_mut().insert(
+                PathBuf::from("a.jpg"),
+                vec![(
+                    ExifTag::DateTimeOriginal,
+                    String::from("2023:12:31 10:20:30"),
+                )]
+                .into_iter()
+                .collect(),
+            );
+            name_generator
+                .generate_names(&[entry("a.jpg", &[])])
+                .remove(0)
+        };
+
+        assert_eq!(Ok(String::from("20231231_1020")), generate("%Y%m%d_%H%M"));
+        assert_eq!(
+            Err(NameGeneratorError::UnformattableDate(String::from("%Y%z"))),
+            generate("%Y%z")
+        );
+    }
+
+    #[test]
+    fn reformats_dates() {
+        let pattern = Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::CaptureGroup(0)),
+                vec![Filter::Date {
+                    input_formats: vec![
+                        String::from("%d%m%Y"),
+                        String::from("%Y-%m-%d"),
+                        String::from("%b-%d-%Y"),
+                    ],
+                    output_format: String::from("%Y-%m-%d"),
+                }],
+            )],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let names = name_generator.generate_names(&[
+            entry("a", &["31122023"]),
+            entry("b", &["2023-12-31"]),
+            entry("c", &["Dec-31-2023"]),
+            entry("d", &["31.12.2023"]),
+        ]);
+
+        assert_eq!(
+            vec![
+                Ok(String::from("2023-12-31")),
+                Ok(String::from("2023-12-31")),
+                Ok(String::from("2023-12-31")),
+                Err(NameGeneratorError::InvalidDate(String::from("31.12.2023"))),
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn fails_with_date_formats_that_need_a_time_zone() {
+        let pattern = Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::CaptureGroup(0)),
+                vec![Filter::Date {
+                    input_formats: vec![String::from("%Y%m%d")],
+                    output_format: String::from("%Y%z"),
+                }],
+            )],
+        };
+
+        let name_generator = NameGeneratorImpl::new(pattern);
+        let name = name_generator
+            .generate_names(&[entry("a", &["20231231"])])
+            .remove(0);
+
+        assert_eq!(
+            Err(NameGeneratorError::UnformattableDate(String::from("%Y%z"))),
+            name
+        );
+    }
+
+    #[test]
+    fn applies_text_normalization_filters() {
+        let filtered = |filters| Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::CaptureGroup(0)),
+                filters,
+            )],
+        };
+
+        let slug = NameGeneratorImpl::new(filtered(vec![Filter::Slug]));
+        assert_eq!(
+            Ok(String::from("mueller-uebersicht-entwurf")),
+            slug.generate_names(&[entry("file", &["Müller – Übersicht (Entwurf)"])])
+                .remove(0)
+        );
+
+        let decomposed = NameGeneratorImpl::new(filtered(vec![Filter::Nfd]));
+        assert_eq!(
+            Ok(String::from("u\u{308}")),
+            decomposed
+                .generate_names(&[entry("file", &["\u{fc}"])])
+                .remove(0)
+        );
+
+        let composed = NameGeneratorImpl::new(filtered(vec![Filter::Nfc]));
+        assert_eq!(
+            Ok(String::from("\u{fc}")),
+            composed
+                .generate_names(&[entry("file", &["u\u{308}"])])
+                .remove(0)
+        );
+    }
 }
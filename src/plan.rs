@@ -0,0 +1,352 @@
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fs::{symlink_metadata, Metadata};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+
+const PLAN_VERSION: u64 = 1;
+
+/// Identifies a file, so that changes between planning and applying can be detected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct FileIdentity {
+    pub(crate) device: u64,
+    pub(crate) inode: u64,
+    pub(crate) size: u64,
+    pub(crate) modified_seconds: i64,
+    pub(crate) modified_nanoseconds: i64,
+}
+
+impl From<&Metadata> for FileIdentity {
+    fn from(metadata: &Metadata) -> Self {
+        Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            size: metadata.size(),
+            modified_seconds: metadata.mtime(),
+            modified_nanoseconds: metadata.mtime_nsec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PlannedRename {
+    pub(crate) source: String,
+    pub(crate) target: String,
+    pub(crate) capture_groups: Vec<String>,
+    pub(crate) identity: FileIdentity,
+}
+
+/// A file that is not renamed, e.g. because its new name is invalid or taken.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Conflict {
+    pub(crate) source: String,
+    pub(crate) target: Option<String>,
+    pub(crate) reason: String,
+}
+
+/// The renames in a directory, computed ahead of time so that they can be reviewed.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Plan {
+    pub(crate) directory: PathBuf,
+    pub(crate) allow_escape: bool,
+    pub(crate) renames: Vec<PlannedRename>,
+    pub(crate) conflicts: Vec<Conflict>,
+}
+
+#[derive(Debug)]
+pub(crate) enum PlanError {
+    IoError(io::Error),
+    JsonError(serde_json::Error),
+    InvalidPlan(&'static str),
+    UnsupportedVersion(u64),
+    /// The files have changed since planning, each with the reason
+    Changed(Vec<String>),
+    /// Targets that would not have been planned, e.g. because the plan was edited
+    InvalidTargets(Vec<String>),
+}
+
+impl Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanError::IoError(error) => write!(f, "Unable to read the plan: {}", error),
+            PlanError::JsonError(error) => write!(f, "Invalid plan: {}", error),
+            PlanError::InvalidPlan(field) => write!(f, "Invalid plan: {} is missing", field),
+            PlanError::UnsupportedVersion(version) => {
+                write!(f, "Plans of version {} are not supported", version)
+            }
+            PlanError::Changed(changes) => write!(
+                f,
+                "The files have changed since planning:\n{}",
+                changes.join("\n")
+            ),
+            PlanError::InvalidTargets(problems) => write!(
+                f,
+                "The plan contains invalid targets:\n{}",
+                problems.join("\n")
+            ),
+        }
+    }
+}
+
+impl Error for PlanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PlanError::IoError(error) => Some(error),
+            PlanError::JsonError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Plan {
+    pub(crate) fn to_json(&self) -> String {
+        let renames: Vec<_> = self
+            .renames
+            .iter()
+            .map(|rename| {
+                json!({
+                    "source": rename.source,
+                    "target": rename.target,
+                    "capture_groups": rename.capture_groups,
+                    "identity": {
+                        "device": rename.identity.device,
+                        "inode": rename.identity.inode,
+                        "size": rename.identity.size,
+                        "modified_seconds": rename.identity.modified_seconds,
+                        "modified_nanoseconds": rename.identity.modified_nanoseconds,
+                    },
+                })
+            })
+            .collect();
+        let conflicts: Vec<_> = self
+            .conflicts
+            .iter()
+            .map(|conflict| {
+                json!({
+                    "source": conflict.source,
+                    "target": conflict.target,
+                    "reason": conflict.reason,
+                })
+            })
+            .collect();
+
+        let plan = json!({
+            "version": PLAN_VERSION,
+            "directory": self.directory.to_string_lossy(),
+            "allow_escape": self.allow_escape,
+            "renames": renames,
+            "conflicts": conflicts,
+        });
+
+        serde_json::to_string_pretty(&plan).expect("Plans are valid JSON")
+    }
+
+    pub(crate) fn from_json(input: &str) -> Result<Self, PlanError> {
+        let plan: Value = serde_json::from_str(input).map_err(PlanError::JsonError)?;
+
+        let version = field(&plan, "version", Value::as_u64)?;
+        if version != PLAN_VERSION {
+            return Err(PlanError::UnsupportedVersion(version));
+        }
+
+        let renames = field(&plan, "renames", Value::as_array)?
+            .iter()
+            .map(|rename| {
+                let identity = field(rename, "identity", Some)?;
+
+                Ok(PlannedRename {
+                    source: string_field(rename, "source")?,
+                    target: string_field(rename, "target")?,
+                    capture_groups: field(rename, "capture_groups", Value::as_array)?
+                        .iter()
+                        .map(|group| group.as_str().map(String::from))
+                        .collect::<Option<_>>()
+                        .ok_or(PlanError::InvalidPlan("capture_groups"))?,
+                    identity: FileIdentity {
+                        device: field(identity, "device", Value::as_u64)?,
+                        inode: field(identity, "inode", Value::as_u64)?,
+                        size: field(identity, "size", Value::as_u64)?,
+                        modified_seconds: field(identity, "modified_seconds", Value::as_i64)?,
+                        modified_nanoseconds: field(
+                            identity,
+                            "modified_nanoseconds",
+                            Value::as_i64,
+                        )?,
+                    },
+                })
+            })
+            .collect::<Result<_, PlanError>>()?;
+
+        let conflicts = field(&plan, "conflicts", Value::as_array)?
+            .iter()
+            .map(|conflict| {
+                Ok(Conflict {
+                    source: string_field(conflict, "source")?,
+                    target: conflict
+                        .get("target")
+                        .and_then(Value::as_str)
+                        .map(String::from),
+                    reason: string_field(conflict, "reason")?,
+                })
+            })
+            .collect::<Result<_, PlanError>>()?;
+
+        Ok(Self {
+            directory: PathBuf::from(string_field(&plan, "directory")?),
+            allow_escape: field(&plan, "allow_escape", Value::as_bool)?,
+            renames,
+            conflicts,
+        })
+    }
+
+    /// Makes sure that every source is still the file that was planned
+    /// and that no other file has taken a target in the meantime.
+    /// Paths are relative to the current directory.
+    pub(crate) fn verify(&self) -> Result<(), PlanError> {
+        let mut targets = HashSet::new();
+        let duplicates: Vec<_> = self
+            .renames
+            .iter()
+            .filter(|rename| !targets.insert(normalize(&rename.target)))
+            .map(|rename| format!("{:?} is the target of more than one file", rename.target))
+            .collect();
+        if !duplicates.is_empty() {
+            return Err(PlanError::InvalidTargets(duplicates));
+        }
+
+        let sources: Vec<_> = self.renames.iter().map(|rename| &rename.source).collect();
+        let mut changes = Vec::new();
+
+        for rename in &self.renames {
+            match symlink_metadata(&rename.source) {
+                Ok(metadata) if FileIdentity::from(&metadata) == rename.identity => {}
+                Ok(_) => changes.push(format!("{:?} has changed", rename.source)),
+                Err(error) => changes.push(format!("{:?}: {}", rename.source, error)),
+            }
+
+            if !sources.contains(&&rename.target) && symlink_metadata(&rename.target).is_ok() {
+                changes.push(format!("{:?} has been created", rename.target));
+            }
+        }
+
+        if changes.is_empty() {
+            Ok(())
+        } else {
+            Err(PlanError::Changed(changes))
+        }
+    }
+}
+
+/// Leaves out `.` components, so that e.g. `./a` and `a` are the same target.
+fn normalize(path: &str) -> PathBuf {
+    Path::new(path)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+fn field<'a, T>(
+    value: &'a Value,
+    name: &'static str,
+    convert: impl Fn(&'a Value) -> Option<T>,
+) -> Result<T, PlanError> {
+    value
+        .get(name)
+        .and_then(convert)
+        .ok_or(PlanError::InvalidPlan(name))
+}
+
+fn string_field(value: &Value, name: &'static str) -> Result<String, PlanError> {
+    field(value, name, Value::as_str).map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    fn plan() -> Plan {
+        Plan {
+            directory: PathBuf::from("/photos"),
+            allow_escape: false,
+            renames: vec![PlannedRename {
+                source: String::from("a.jpg"),
+                target: String::from("holiday/a.jpg"),
+                capture_groups: vec![String::from("a")],
+                identity: FileIdentity {
+                    device: 2049,
+                    inode: 1234,
+                    size: 4096,
+                    modified_seconds: 1_700_000_000,
+                    modified_nanoseconds: 123_456_789,
+                },
+            }],
+            conflicts: vec![Conflict {
+                source: String::from("b.jpg"),
+                target: None,
+                reason: String::from("Invalid character: ':'"),
+            }],
+        }
+    }
+
+    #[test]
+    fn converts_plans_to_json_and_back() {
+        let plan = plan();
+
+        assert_eq!(plan, Plan::from_json(&plan.to_json()).unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_plans() {
+        assert!(matches!(
+            Plan::from_json("{\"version\": 2}"),
+            Err(PlanError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            Plan::from_json("{\"version\": 1, \"renames\": []}"),
+            Err(PlanError::InvalidPlan("conflicts"))
+        ));
+    }
+
+    #[test]
+    fn detects_changes() {
+        let directory = temp_dir().join(format!("mvr-plan-{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+        let source = directory.join("a.jpg");
+        write(&source, "a").unwrap();
+
+        let mut plan = plan();
+        plan.renames[0].source = source.to_string_lossy().into_owned();
+        plan.renames[0].identity = FileIdentity::from(&symlink_metadata(&source).unwrap());
+        plan.renames[0].target = directory.join("b.jpg").to_string_lossy().into_owned();
+        let unchanged = plan.verify();
+
+        write(&source, "changed").unwrap();
+        write(directory.join("b.jpg"), "b").unwrap();
+        let changed = plan.verify();
+
+        remove_dir_all(&directory).unwrap();
+
+        assert!(unchanged.is_ok());
+        assert!(matches!(changed, Err(PlanError::Changed(changes)) if changes.len() == 2));
+    }
+
+    #[test]
+    fn rejects_duplicate_targets() {
+        let mut plan = plan();
+        let mut rename = plan.renames[0].clone();
+        rename.source = String::from("b.jpg");
+        rename.target = String::from("./holiday/a.jpg");
+        plan.renames.push(rename);
+
+        assert!(matches!(
+            plan.verify(),
+            Err(PlanError::InvalidTargets(problems)) if problems.len() == 1
+        ));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Debug, Display};
+use std::fs::{read_to_string, remove_file, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+#[cfg(test)]
+use mockiato::mockable;
+
+#[derive(Debug)]
+pub(crate) enum EditError {
+    IoError(io::Error),
+    EditorFailed(process::ExitStatus),
+    InvalidLine(usize),
+    UnknownEntry(usize),
+    DuplicateEntry(usize),
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EditError::IoError(error) => write!(f, "Unable to edit the new names: {}", error),
+            EditError::EditorFailed(status) => write!(f, "The editor failed ({})", status),
+            EditError::InvalidLine(line) => write!(
+                f,
+                "Line {} is not a number followed by a tab and the new name",
+                line
+            ),
+            EditError::UnknownEntry(number) => write!(f, "There is no file number {}", number),
+            EditError::DuplicateEntry(number) => {
+                write!(f, "File number {} is listed more than once", number)
+            }
+        }
+    }
+}
+
+impl Error for EditError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EditError::IoError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg_attr(test, mockable)]
+pub(crate) trait PlanEditor: Debug {
+    /// Lets the user change the text and returns the result.
+    fn edit(&self, text: &str) -> Result<String, EditError>;
+}
+
+const TERMINAL: &str = "/dev/tty";
+
+/// Opens a temporary file in `$VISUAL` or `$EDITOR`, falling back to `vi`.
+#[derive(Debug, Default)]
+pub(crate) struct ExternalEditor;
+
+impl PlanEditor for ExternalEditor {
+    fn edit(&self, text: &str) -> Result<String, EditError> {
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or_else(|_| String::from("vi"));
+        let path = create_temporary_file(text).map_err(EditError::IoError)?;
+
+        // The shell splits editor commands like `code --wait`
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&path);
+
+        // The standard input may be taken by a list of files
+        if let Ok(terminal) = File::open(TERMINAL) {
+            command.stdin(terminal);
+        }
+
+        let status = command.status();
+        let edited_text = read_to_string(&path);
+        let _ = remove_file(&path);
+
+        match status.map_err(EditError::IoError)? {
+            status if status.success() => edited_text.map_err(EditError::IoError),
+            status => Err(EditError::EditorFailed(status)),
+        }
+    }
+}
+
+/// Creates a file with an unpredictable name that only the user can access. Existing files,
+/// including symlinks planted by other users, are never opened.
+fn create_temporary_file(text: &str) -> io::Result<PathBuf> {
+    loop {
+        // Each RandomState is seeded randomly
+        let suffix = RandomState::new().build_hasher().finish();
+        let path = env::temp_dir().join(format!("mvr-{:016x}.txt", suffix));
+
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            result => result?,
+        };
+
+        return match file.write_all(text.as_bytes()) {
+            Ok(()) => Ok(path),
+            Err(error) => {
+                let _ = remove_file(&path);
+                Err(error)
+            }
+        };
+    }
+}
+
+const HEADER: &str = "\
+# Edit the new names after the numbers and save the file to rename the files.
+# Delete a line to skip its file. Lines starting with # are ignored.
+";
+
+/// Lists the new names numbered, each preceded by a comment with the old name.
+pub(crate) fn format_plan<'a>(renames: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let mut text = String::from(HEADER);
+
+    for (number, (old_name, new_name)) in renames.into_iter().enumerate() {
+        text.push_str(&format!("\n# {}\n{}\t{}\n", old_name, number + 1, new_name));
+    }
+
+    text
+}
+
+/// Returns the edited new name of each of the `count` files, or `None` if its line was deleted.
+pub(crate) fn parse_plan(text: &str, count: usize) -> Result<Vec<Option<String>>, EditError> {
+    let mut new_names = vec![None; count];
+    let mut seen = HashSet::new();
+
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (number, new_name) = line
+            .split_once('\t')
+            .and_then(|(number, new_name)| Some((number.trim().parse::<usize>().ok()?, new_name)))
+            .filter(|(_, new_name)| !new_name.is_empty())
+            .ok_or(EditError::InvalidLine(index + 1))?;
+
+        if number == 0 || number > count {
+            return Err(EditError::UnknownEntry(number));
+        }
+
+        if !seen.insert(number) {
+            return Err(EditError::DuplicateEntry(number));
+        }
+
+        new_names[number - 1] = Some(String::from(new_name));
+    }
+
+    Ok(new_names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_private_temporary_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = create_temporary_file("text").unwrap();
+        let second = create_temporary_file("text").unwrap();
+        let contents = read_to_string(&first);
+        let mode = std::fs::metadata(&first).map(|metadata| metadata.permissions().mode());
+        remove_file(&first).unwrap();
+        remove_file(&second).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!("text", contents.unwrap());
+        assert_eq!(0o600, mode.unwrap() & 0o777);
+    }
+
+    #[test]
+    fn formats_and_parses_plans() {
+        let text = format_plan(vec![("a.txt", "b.txt"), ("c.txt", "d.txt")]);
+
+        assert!(text.ends_with("\n# a.txt\n1\tb.txt\n\n# c.txt\n2\td.txt\n"));
+        assert_eq!(
+            vec![Some(String::from("b.txt")), Some(String::from("d.txt"))],
+            parse_plan(&text, 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_edited_plans() {
+        assert_eq!(
+            vec![None, Some(String::from("new name.txt"))],
+            parse_plan("# comment\n\n2\tnew name.txt\n", 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_plans() {
+        assert!(matches!(
+            parse_plan("1 a.txt", 1),
+            Err(EditError::InvalidLine(1))
+        ));
+        assert!(matches!(
+            parse_plan("\n1\t", 1),
+            Err(EditError::InvalidLine(2))
+        ));
+        assert!(matches!(
+            parse_plan("2\ta.txt", 1),
+            Err(EditError::UnknownEntry(2))
+        ));
+        assert!(matches!(
+            parse_plan("1\ta.txt\n1\tb.txt", 1),
+            Err(EditError::DuplicateEntry(1))
+        ));
+    }
+}
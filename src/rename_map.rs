@@ -0,0 +1,247 @@
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::io;
+use std::io::{BufRead, Read};
+use std::path::Path;
+
+/// The formats of files that list old and new names.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum MapFormat {
+    /// One `old<TAB>new` pair per line
+    Tsv,
+    /// One `old,new` record per line, fields may be quoted
+    Csv,
+    /// One `["old", "new"]` or `{"from": "old", "to": "new"}` per line
+    JsonLines,
+    /// `old\0new\0` pairs, e.g. for names that contain line breaks
+    NulSeparated,
+}
+
+impl MapFormat {
+    /// Guesses the format from the extension, the standard input (`-`) is read NUL separated.
+    pub(crate) fn of_path(path: &Path) -> Self {
+        if path == Path::new("-") {
+            return MapFormat::NulSeparated;
+        }
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => MapFormat::Csv,
+            Some("json") | Some("jsonl") | Some("ndjson") => MapFormat::JsonLines,
+            _ => MapFormat::Tsv,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum MapError {
+    IoError(io::Error),
+    CsvError(csv::Error),
+    JsonError(usize, serde_json::Error),
+    InvalidEntry(usize),
+}
+
+impl Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::IoError(error) => write!(f, "Unable to read the rename map: {}", error),
+            MapError::CsvError(error) => write!(f, "Invalid rename map: {}", error),
+            MapError::JsonError(line, error) => {
+                write!(f, "Invalid JSON on line {}: {}", line, error)
+            }
+            MapError::InvalidEntry(number) => write!(
+                f,
+                "Entry {} of the rename map is not a pair of an old and a new name",
+                number
+            ),
+        }
+    }
+}
+
+impl Error for MapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MapError::IoError(error) => Some(error),
+            MapError::CsvError(error) => Some(error),
+            MapError::JsonError(_, error) => Some(error),
+            MapError::InvalidEntry(_) => None,
+        }
+    }
+}
+
+/// Reads pairs of old and new names.
+pub(crate) fn read_map(
+    reader: impl Read,
+    format: MapFormat,
+) -> Result<Vec<(String, String)>, MapError> {
+    match format {
+        MapFormat::Tsv => read_delimited(reader, b'\t', false),
+        MapFormat::Csv => read_delimited(reader, b',', true),
+        MapFormat::JsonLines => read_json_lines(reader),
+        MapFormat::NulSeparated => read_nul_separated(reader),
+    }
+}
+
+fn read_delimited(
+    reader: impl Read,
+    delimiter: u8,
+    quoting: bool,
+) -> Result<Vec<(String, String)>, MapError> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(delimiter)
+        .quoting(quoting)
+        .comment(Some(b'#'))
+        .from_reader(reader)
+        .records()
+        .enumerate()
+        .map(|(index, record)| {
+            let record = record.map_err(MapError::CsvError)?;
+            match (record.get(0), record.get(1), record.len()) {
+                (Some(old_name), Some(new_name), 2) => {
+                    Ok((String::from(old_name), String::from(new_name)))
+                }
+                _ => Err(MapError::InvalidEntry(index + 1)),
+            }
+        })
+        .collect()
+}
+
+fn read_json_lines(reader: impl Read) -> Result<Vec<(String, String)>, MapError> {
+    let mut pairs = Vec::new();
+
+    for (index, line) in io::BufReader::new(reader).lines().enumerate() {
+        let line = line.map_err(MapError::IoError)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value: Value =
+            serde_json::from_str(&line).map_err(|error| MapError::JsonError(index + 1, error))?;
+        let pair = match &value {
+            Value::Array(names) if names.len() == 2 => (names[0].as_str(), names[1].as_str()),
+            Value::Object(names) if names.len() == 2 => (
+                names.get("from").and_then(Value::as_str),
+                names.get("to").and_then(Value::as_str),
+            ),
+            _ => (None, None),
+        };
+
+        match pair {
+            (Some(old_name), Some(new_name)) => {
+                pairs.push((String::from(old_name), String::from(new_name)))
+            }
+            _ => return Err(MapError::InvalidEntry(pairs.len() + 1)),
+        }
+    }
+
+    Ok(pairs)
+}
+
+fn read_nul_separated(mut reader: impl Read) -> Result<Vec<(String, String)>, MapError> {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input).map_err(MapError::IoError)?;
+
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let names = input
+        .strip_suffix(b"\0")
+        .unwrap_or(&input)
+        .split(|byte| *byte == b'\0')
+        .map(|name| String::from_utf8(name.to_vec()).ok())
+        .collect::<Vec<_>>();
+
+    names
+        .chunks(2)
+        .enumerate()
+        .map(|(index, pair)| match pair {
+            [Some(old_name), Some(new_name)] => Ok((old_name.clone(), new_name.clone())),
+            _ => Err(MapError::InvalidEntry(index + 1)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(old_name, new_name)| (String::from(*old_name), String::from(*new_name)))
+            .collect()
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(MapFormat::Csv, MapFormat::of_path(Path::new("map.csv")));
+        assert_eq!(
+            MapFormat::JsonLines,
+            MapFormat::of_path(Path::new("map.jsonl"))
+        );
+        assert_eq!(MapFormat::Tsv, MapFormat::of_path(Path::new("map.tsv")));
+        assert_eq!(MapFormat::NulSeparated, MapFormat::of_path(Path::new("-")));
+    }
+
+    #[test]
+    fn reads_delimited_maps() {
+        assert_eq!(
+            pairs(&[("a \"b\".txt", "c.txt"), ("d, e.txt", "f.txt")]),
+            read_map(
+                &b"# old\tnew\na \"b\".txt\tc.txt\nd, e.txt\tf.txt\n"[..],
+                MapFormat::Tsv
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            pairs(&[("a, b.txt", "c.txt")]),
+            read_map(&b"\"a, b.txt\",c.txt\n"[..], MapFormat::Csv).unwrap()
+        );
+        assert!(matches!(
+            read_map(&b"a.txt\tb.txt\tc.txt\n"[..], MapFormat::Tsv),
+            Err(MapError::InvalidEntry(1))
+        ));
+    }
+
+    #[test]
+    fn reads_json_lines() {
+        assert_eq!(
+            pairs(&[("a.txt", "b.txt"), ("c\n.txt", "d.txt")]),
+            read_map(
+                &b"[\"a.txt\", \"b.txt\"]\n\n{\"from\": \"c\\n.txt\", \"to\": \"d.txt\"}\n"[..],
+                MapFormat::JsonLines
+            )
+            .unwrap()
+        );
+        assert!(matches!(
+            read_map(&b"[\"a.txt\"]"[..], MapFormat::JsonLines),
+            Err(MapError::InvalidEntry(1))
+        ));
+        assert!(matches!(
+            read_map(&b"[\"a.txt\""[..], MapFormat::JsonLines),
+            Err(MapError::JsonError(1, _))
+        ));
+    }
+
+    #[test]
+    fn reads_nul_separated_pairs() {
+        assert_eq!(
+            pairs(&[("a\n.txt", "b.txt"), ("c.txt", "d.txt")]),
+            read_map(
+                &b"a\n.txt\0b.txt\0c.txt\0d.txt\0"[..],
+                MapFormat::NulSeparated
+            )
+            .unwrap()
+        );
+        assert!(read_map(&b""[..], MapFormat::NulSeparated)
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            read_map(&b"a.txt\0b.txt\0c.txt"[..], MapFormat::NulSeparated),
+            Err(MapError::InvalidEntry(2))
+        ));
+    }
+}
@@ -1,21 +1,42 @@
+use crate::confirmation::{Answer, Prompter};
+use crate::console::{Console, StandardConsole};
+use crate::executor::{plan_steps, Executor, Rename};
+use crate::git::{GitError, Repository};
+use crate::limits::{LimitChecker, LimitError};
 use crate::matcher::Matcher;
-use crate::name_generator::{NameGenerator, NameGeneratorError};
+use crate::metadata_filter::{Expression, FileProperties};
+use crate::name_generator::{Entry, NameGenerator, NameGeneratorError};
+use crate::plan::{Conflict, FileIdentity, Plan, PlanError, PlannedRename};
+use crate::plan_editor::{format_plan, parse_plan, EditError, PlanEditor};
+use crate::sanitization::{SanitizationError, Sanitizer};
+use crate::script::{create_script, ScriptFlavor};
+use crate::sorting::SortOrder;
+use crate::traversal::{TraversalError, TraversalGuard};
 use glob::glob;
+use std::collections::HashSet;
 use std::error::Error;
 use std::fmt::Display;
-use std::fs::{create_dir_all, rename};
-use std::path::Path;
+use std::fs::symlink_metadata;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
 use std::{fmt, io};
 
 #[cfg(test)]
 use mockiato::mockable;
-use std::env::set_current_dir;
+use std::env::{current_dir, set_current_dir};
 
 #[derive(Debug)]
 pub(crate) enum RenamerError {
     IoError(io::Error),
     MatcherError,
     NameGeneratorError(NameGeneratorError),
+    SanitizationError(SanitizationError),
+    LimitError(LimitError),
+    TraversalError(TraversalError),
+    EditError(EditError),
+    GitError(GitError),
+    TargetTaken(String),
     InvalidFileName,
     InternalError(Box<dyn Error>),
 }
@@ -25,7 +46,21 @@ impl Display for RenamerError {
         let message = match self {
             RenamerError::IoError(_) => "An io error occurred",
             RenamerError::MatcherError => "Could not match name against file",
-            RenamerError::NameGeneratorError(_) => "Unable to create the new file name",
+            RenamerError::NameGeneratorError(error) => {
+                return write!(f, "Unable to create the new file name: {}", error)
+            }
+            RenamerError::SanitizationError(error) => {
+                return write!(f, "The new file name is not valid: {}", error)
+            }
+            RenamerError::LimitError(error) => {
+                return write!(f, "The new file name is too long: {}", error)
+            }
+            RenamerError::TraversalError(error) => return write!(f, "{}", error),
+            RenamerError::EditError(error) => return write!(f, "{}", error),
+            RenamerError::GitError(error) => return write!(f, "{}", error),
+            RenamerError::TargetTaken(new_name) => {
+                return write!(f, "{:?} is already taken", new_name)
+            }
             RenamerError::InvalidFileName => "Invalid file name. Make sure it is is valid unicode",
             RenamerError::InternalError(_) => "An internal error occured",
         };
@@ -34,42 +69,507 @@ impl Display for RenamerError {
     }
 }
 
-impl Error for RenamerError {}
+impl Error for RenamerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenamerError::IoError(error) => Some(error),
+            RenamerError::NameGeneratorError(error) => Some(error),
+            RenamerError::SanitizationError(error) => Some(error),
+            RenamerError::LimitError(error) => Some(error),
+            RenamerError::TraversalError(error) => Some(error),
+            RenamerError::EditError(error) => Some(error),
+            RenamerError::GitError(error) => Some(error),
+            RenamerError::InternalError(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 #[cfg_attr(test, mockable)]
 pub(crate) trait Renamer {
     fn rename_files_in_directory(&self, directory: &Path) -> Result<(), Box<dyn Error>>;
 }
 
+/// A pattern whose matches are left untouched, even if they match the matching pattern.
+#[derive(Debug)]
+pub(crate) struct Exclusion {
+    pattern: String,
+    matcher: Box<dyn Matcher>,
+}
+
+impl Exclusion {
+    pub(crate) fn new(pattern: String, matcher: Box<dyn Matcher>) -> Self {
+        Self { pattern, matcher }
+    }
+
+    fn excludes(&self, name: &str) -> bool {
+        self.matcher.match_against(name).is_ok()
+    }
+}
+
+/// A file that matched the glob and passed the exclusions and the filter.
+#[derive(Debug)]
+struct Candidate {
+    path: PathBuf,
+    name: String,
+    properties: FileProperties,
+    /// The contents of the groups of the matching pattern, if any
+    capture_groups: Vec<String>,
+}
+
+/// Settings that control how files are renamed, independent of the patterns.
+#[derive(Clone, Debug)]
+pub(crate) struct RenamerOptions {
+    pub(crate) sort_order: SortOrder,
+    pub(crate) sanitizer: Option<Sanitizer>,
+    /// Shorten file names that exceed the limits of the file system instead of skipping them
+    pub(crate) truncate_long_names: bool,
+    /// Allow new names that lead out of the directory, e.g. `../file`
+    pub(crate) allow_escape: bool,
+    pub(crate) dry_run: bool,
+    /// Print the renames as NUL terminated pairs of old and new names
+    pub(crate) null_separated: bool,
+    /// Print the plan as JSON instead of renaming
+    pub(crate) plan_only: bool,
+    /// Print a script that performs the renames instead of renaming
+    pub(crate) emit_script: Option<ScriptFlavor>,
+    /// Move tracked files in the index of the enclosing repository like `git mv`
+    pub(crate) git: bool,
+    /// Rename tracked files even if they have uncommitted changes
+    pub(crate) force: bool,
+    /// Where renames, plans and scripts as well as diagnostics are written
+    pub(crate) console: Rc<dyn Console>,
+}
+
+impl Default for RenamerOptions {
+    fn default() -> Self {
+        Self {
+            sort_order: SortOrder::default(),
+            sanitizer: None,
+            truncate_long_names: false,
+            allow_escape: false,
+            dry_run: false,
+            null_separated: false,
+            plan_only: false,
+            emit_script: None,
+            git: false,
+            force: false,
+            console: Rc::new(StandardConsole),
+        }
+    }
+}
+
+/// Where the files to rename come from.
+#[derive(Debug)]
+pub(crate) enum CandidateSource {
+    /// The files matching the glob in the current directory
+    Glob(String),
+    /// The given paths, of which only those matching the pattern are renamed
+    Paths(Vec<PathBuf>),
+}
+
 #[derive(Debug)]
 pub(crate) struct RenamerImpl {
-    matching_pattern_glob: String,
+    candidate_source: CandidateSource,
     matcher: Box<dyn Matcher>,
+    exclusions: Vec<Exclusion>,
+    filter: Option<Expression>,
     name_generator: Box<dyn NameGenerator>,
+    options: RenamerOptions,
+    review: Review,
+}
+
+/// Ways for the user to review the new names before any file is renamed.
+#[derive(Debug, Default)]
+pub(crate) struct Review {
+    /// Opens the new names in an editor if set
+    pub(crate) editor: Option<Box<dyn PlanEditor>>,
+    /// Asks before renaming each file if set
+    pub(crate) prompter: Option<Box<dyn Prompter>>,
 }
 
 impl RenamerImpl {
     pub(crate) fn new(
-        matching_pattern_glob: String,
+        candidate_source: CandidateSource,
         matcher: Box<dyn Matcher>,
+        exclusions: Vec<Exclusion>,
+        filter: Option<Expression>,
         name_generator: Box<dyn NameGenerator>,
+        options: RenamerOptions,
+        review: Review,
     ) -> Self {
         Self {
-            matching_pattern_glob,
+            candidate_source,
             matcher,
+            exclusions,
+            filter,
             name_generator,
+            options,
+            review,
+        }
+    }
+
+    fn find_exclusion(&self, name: &str) -> Option<&Exclusion> {
+        self.exclusions
+            .iter()
+            .find(|exclusion| exclusion.excludes(name))
+    }
+
+    fn satisfies_filter(&self, properties: &FileProperties, now: SystemTime) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|filter| filter.evaluate(properties, now))
+    }
+
+    fn find_candidates(&self) -> Result<Vec<Candidate>, RenamerError> {
+        let now = SystemTime::now();
+        let mut candidates = Vec::new();
+
+        let paths: Vec<PathBuf> = match &self.candidate_source {
+            // TODO: Make sure globs work the same way as the matcher
+            CandidateSource::Glob(matching_pattern_glob) => glob(matching_pattern_glob)
+                .map_err(|error| RenamerError::InternalError(Box::new(error)))?
+                .filter_map(Result::ok)
+                .collect(),
+            CandidateSource::Paths(paths) => paths.clone(),
+        };
+        let is_given = matches!(self.candidate_source, CandidateSource::Paths(_));
+
+        for path in paths {
+            let name = match path.to_str() {
+                Some(name) => String::from(name),
+                None if is_given => {
+                    self.options.console.report(&format!(
+                        "Ignoring file: {:?} ({})",
+                        path,
+                        RenamerError::InvalidFileName
+                    ));
+                    continue;
+                }
+                None => return Err(RenamerError::InvalidFileName),
+            };
+
+            // Given paths are filtered by the pattern instead of the glob
+            if is_given && self.matcher.match_against(&name).is_err() {
+                continue;
+            }
+
+            if let Some(exclusion) = self.find_exclusion(&name) {
+                // Reported separately, as the output can be a plan or a list of renames
+                self.options.console.report(&format!(
+                    "Excluding {:?} (matches {:?})",
+                    name, exclusion.pattern
+                ));
+                continue;
+            }
+
+            let metadata = match symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(error) if is_given => {
+                    self.options
+                        .console
+                        .report(&format!("Ignoring file: {:?} ({})", name, error));
+                    continue;
+                }
+                Err(error) => return Err(RenamerError::IoError(error)),
+            };
+            let properties = FileProperties::from(&metadata);
+
+            if !self.satisfies_filter(&properties, now) {
+                continue;
+            }
+
+            candidates.push(Candidate {
+                path,
+                name,
+                properties,
+                capture_groups: Vec::new(),
+            });
         }
+
+        candidates.sort_by(|first, second| {
+            self.options.sort_order.compare(
+                (&first.name, &first.properties),
+                (&second.name, &second.properties),
+            )
+        });
+
+        Ok(candidates)
     }
 
-    fn create_new_name(&self, old_name: &str) -> Result<String, RenamerError> {
+    fn create_entry(&self, candidate: &Candidate) -> Result<Entry, RenamerError> {
         let capture_groups = self
             .matcher
-            .match_against(old_name)
+            .match_against(&candidate.name)
             .map_err(|_| RenamerError::MatcherError)?;
 
-        self.name_generator
-            .generate_name(capture_groups)
-            .map_err(RenamerError::NameGeneratorError)
+        Ok(Entry {
+            path: candidate.path.clone(),
+            capture_groups,
+            properties: candidate.properties.clone(),
+        })
+    }
+}
+
+/// Validates, reviews and performs renames, regardless of where the new names come from.
+#[derive(Debug)]
+struct Applier<'a> {
+    options: &'a RenamerOptions,
+    review: &'a Review,
+}
+
+impl Applier<'_> {
+    /// Renames the candidates relative to the current directory.
+    fn apply(
+        &self,
+        named_candidates: Vec<(Candidate, Result<String, NameGeneratorError>)>,
+    ) -> Result<(), RenamerError> {
+        let root = current_dir().map_err(RenamerError::IoError)?;
+        let traversal_guard = if self.options.allow_escape {
+            None
+        } else {
+            Some(TraversalGuard::new(&root).map_err(RenamerError::IoError)?)
+        };
+        let mut limit_checker = LimitChecker::new(root.clone(), self.options.truncate_long_names);
+
+        let mut conflicts = Vec::new();
+
+        // All new names are validated before the first file is renamed
+        let renames: Vec<_> = named_candidates
+            .into_iter()
+            .filter_map(|(candidate, new_name)| {
+                let target = new_name.as_ref().ok().cloned();
+
+                match self.validate_new_name(new_name, traversal_guard.as_ref(), &mut limit_checker)
+                {
+                    Ok(new_name) => Some((candidate, new_name)),
+                    Err(error) => {
+                        self.options
+                            .console
+                            .report(&format!("Ignoring file: {:?} ({})", candidate.name, error));
+                        conflicts.push(Conflict {
+                            source: candidate.name,
+                            target,
+                            reason: error.to_string(),
+                        });
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let renames = match &self.review.editor {
+            Some(editor) if !renames.is_empty() => self.edit_renames(
+                editor.as_ref(),
+                renames,
+                traversal_guard.as_ref(),
+                &mut limit_checker,
+            )?,
+            _ => renames,
+        };
+
+        let renames = match &self.review.prompter {
+            Some(prompter) => self.confirm_renames(
+                prompter.as_ref(),
+                renames,
+                traversal_guard.as_ref(),
+                &mut limit_checker,
+            )?,
+            None => renames,
+        };
+
+        let renames: Vec<_> = renames
+            .into_iter()
+            .filter(|(candidate, new_name)| candidate.name != *new_name)
+            .collect();
+
+        if self.options.plan_only {
+            let plan = create_plan(root, self.options.allow_escape, renames, conflicts)?;
+            self.options
+                .console
+                .write_output(&format!("{}\n", plan.to_json()));
+            return Ok(());
+        }
+
+        execute_renames(
+            renames
+                .into_iter()
+                .map(|(candidate, new_name)| Rename {
+                    source: candidate.path,
+                    target: PathBuf::from(new_name),
+                })
+                .collect(),
+            &root,
+            self.options,
+        )
+    }
+
+    fn validate_new_name(
+        &self,
+        new_name: Result<String, NameGeneratorError>,
+        traversal_guard: Option<&TraversalGuard>,
+        limit_checker: &mut LimitChecker,
+    ) -> Result<String, RenamerError> {
+        let new_name = new_name.map_err(RenamerError::NameGeneratorError)?;
+
+        let new_name = match &self.options.sanitizer {
+            Some(sanitizer) => sanitizer
+                .sanitize(&new_name)
+                .map_err(RenamerError::SanitizationError)?,
+            None => new_name,
+        };
+
+        if let Some(traversal_guard) = traversal_guard {
+            traversal_guard
+                .check(&new_name)
+                .map_err(RenamerError::TraversalError)?;
+        }
+
+        limit_checker
+            .check(&new_name)
+            .map_err(RenamerError::LimitError)
+    }
+
+    /// Validates a name entered by the user like a generated one and makes sure that
+    /// it is neither the target of another rename nor an existing file that stays in place.
+    fn validate_edited_name(
+        &self,
+        old_name: &str,
+        edited_name: String,
+        (sources, targets): (&HashSet<String>, &HashSet<String>),
+        traversal_guard: Option<&TraversalGuard>,
+        limit_checker: &mut LimitChecker,
+    ) -> Result<String, RenamerError> {
+        let edited_name =
+            self.validate_new_name(Ok(edited_name), traversal_guard, limit_checker)?;
+
+        let is_taken = targets.contains(&edited_name)
+            || (!sources.contains(&edited_name) && symlink_metadata(&edited_name).is_ok());
+
+        if is_taken && edited_name != old_name {
+            Err(RenamerError::TargetTaken(edited_name))
+        } else {
+            Ok(edited_name)
+        }
+    }
+
+    /// Lets the user change the new names in an editor. Files whose lines
+    /// were deleted are skipped and changed names are validated again.
+    fn edit_renames(
+        &self,
+        editor: &dyn PlanEditor,
+        renames: Vec<(Candidate, String)>,
+        traversal_guard: Option<&TraversalGuard>,
+        limit_checker: &mut LimitChecker,
+    ) -> Result<Vec<(Candidate, String)>, RenamerError> {
+        let plan = format_plan(
+            renames
+                .iter()
+                .map(|(candidate, new_name)| (candidate.name.as_str(), new_name.as_str())),
+        );
+        let edited_names = editor
+            .edit(&plan)
+            .and_then(|edited_plan| parse_plan(&edited_plan, renames.len()))
+            .map_err(RenamerError::EditError)?;
+
+        let sources: HashSet<_> = renames
+            .iter()
+            .zip(&edited_names)
+            .filter(|(_, edited_name)| edited_name.is_some())
+            .map(|((candidate, _), _)| candidate.name.clone())
+            .collect();
+        let mut targets: HashSet<_> = renames
+            .iter()
+            .zip(&edited_names)
+            .filter(|((_, new_name), edited_name)| edited_name.as_ref() == Some(new_name))
+            .map(|((_, new_name), _)| new_name.clone())
+            .collect();
+        let mut edited = Vec::with_capacity(renames.len());
+
+        for ((candidate, new_name), edited_name) in renames.into_iter().zip(edited_names) {
+            match edited_name {
+                None => {}
+                Some(edited_name) if edited_name == new_name => edited.push((candidate, new_name)),
+                Some(edited_name) => match self.validate_edited_name(
+                    &candidate.name,
+                    edited_name,
+                    (&sources, &targets),
+                    traversal_guard,
+                    limit_checker,
+                ) {
+                    Ok(edited_name) => {
+                        targets.insert(edited_name.clone());
+                        edited.push((candidate, edited_name));
+                    }
+                    Err(error) => self
+                        .options
+                        .console
+                        .report(&format!("Ignoring file: {:?} ({})", candidate.name, error)),
+                },
+            }
+        }
+
+        Ok(edited)
+    }
+
+    /// Lets the user accept, skip or edit each rename. Edited names are validated again.
+    fn confirm_renames(
+        &self,
+        prompter: &dyn Prompter,
+        renames: Vec<(Candidate, String)>,
+        traversal_guard: Option<&TraversalGuard>,
+        limit_checker: &mut LimitChecker,
+    ) -> Result<Vec<(Candidate, String)>, RenamerError> {
+        // Only the files that are accepted so far will be moved away
+        let mut sources = HashSet::new();
+        let mut targets: HashSet<_> = renames
+            .iter()
+            .map(|(_, new_name)| new_name.clone())
+            .collect();
+        let mut confirmed = Vec::with_capacity(renames.len());
+        let mut is_confirming = true;
+
+        for (candidate, mut new_name) in renames {
+            let mut is_accepted = true;
+
+            while is_confirming {
+                match prompter
+                    .confirm(&candidate.name, &new_name)
+                    .map_err(RenamerError::IoError)?
+                {
+                    Answer::Yes => break,
+                    Answer::All => is_confirming = false,
+                    Answer::No => {
+                        is_accepted = false;
+                        break;
+                    }
+                    Answer::Quit => return Ok(confirmed),
+                    Answer::Edit(edited_name) => match self.validate_edited_name(
+                        &candidate.name,
+                        edited_name,
+                        (&sources, &targets),
+                        traversal_guard,
+                        limit_checker,
+                    ) {
+                        Ok(edited_name) => {
+                            targets.remove(&new_name);
+                            targets.insert(edited_name.clone());
+                            new_name = edited_name;
+                        }
+                        Err(error) => prompter.reject(&error.to_string()),
+                    },
+                }
+            }
+
+            if is_accepted {
+                sources.insert(candidate.name.clone());
+                confirmed.push((candidate, new_name));
+            } else {
+                targets.remove(&new_name);
+            }
+        }
+
+        Ok(confirmed)
     }
 }
 
@@ -77,33 +577,684 @@ impl Renamer for RenamerImpl {
     fn rename_files_in_directory(&self, directory: &Path) -> Result<(), Box<dyn Error>> {
         set_current_dir(directory).map_err(|error| RenamerError::InternalError(Box::new(error)))?;
 
-        // TODO: Make sure globs work the same way as the matcher
-        for old_path in glob(self.matching_pattern_glob.as_ref())
-            .map_err(|error| RenamerError::InternalError(Box::new(error)))?
-            .filter_map(Result::ok)
-        {
-            let old_name = old_path.to_str().ok_or(RenamerError::InvalidFileName)?;
-
-            let new_name = match self.create_new_name(old_name) {
-                Ok(new_name) => new_name,
-                Err(_) => {
-                    eprintln!("Ignoring file: {:?}", old_name);
-                    continue;
+        let (candidates, entries): (Vec<_>, Vec<_>) = self
+            .find_candidates()?
+            .into_iter()
+            .filter_map(|mut candidate| match self.create_entry(&candidate) {
+                Ok(entry) => {
+                    candidate.capture_groups = entry
+                        .capture_groups
+                        .iter()
+                        .map(|group| group.contents.clone())
+                        .collect();
+                    Some((candidate, entry))
                 }
-            };
+                Err(error) => {
+                    self.options
+                        .console
+                        .report(&format!("Ignoring file: {:?} ({})", candidate.name, error));
+                    None
+                }
+            })
+            .unzip();
+
+        let new_names = self.name_generator.generate_names(&entries);
+
+        let applier = Applier {
+            options: &self.options,
+            review: &self.review,
+        };
+        applier.apply(candidates.into_iter().zip(new_names).collect())?;
+
+        Ok(())
+    }
+}
+
+/// Renames files according to explicit pairs of old and new names instead of patterns.
+#[derive(Debug)]
+pub(crate) struct MappingRenamer {
+    mappings: Vec<(String, String)>,
+    options: RenamerOptions,
+    review: Review,
+}
+
+impl MappingRenamer {
+    pub(crate) fn new(
+        mappings: Vec<(String, String)>,
+        options: RenamerOptions,
+        review: Review,
+    ) -> Self {
+        Self {
+            mappings,
+            options,
+            review,
+        }
+    }
+}
 
-            println!("Renaming {:?} → {:?}", &old_name, &new_name);
+impl Renamer for MappingRenamer {
+    fn rename_files_in_directory(&self, directory: &Path) -> Result<(), Box<dyn Error>> {
+        set_current_dir(directory).map_err(|error| RenamerError::InternalError(Box::new(error)))?;
 
-            let new_path = Path::new(&new_name);
-            if new_path.exists() {
-                eprintln!("Path already exists. Skipping...");
+        let mut old_names = HashSet::new();
+        let named_candidates = self
+            .mappings
+            .iter()
+            .filter_map(|(old_name, new_name)| {
+                if !old_names.insert(old_name) {
+                    self.options.console.report(&format!(
+                        "Ignoring file: {:?} (listed more than once)",
+                        old_name
+                    ));
+                    return None;
+                }
+
+                match symlink_metadata(old_name) {
+                    Ok(metadata) => Some((
+                        Candidate {
+                            path: PathBuf::from(old_name),
+                            name: old_name.clone(),
+                            properties: FileProperties::from(&metadata),
+                            capture_groups: Vec::new(),
+                        },
+                        Ok(new_name.clone()),
+                    )),
+                    Err(error) => {
+                        self.options
+                            .console
+                            .report(&format!("Ignoring file: {:?} ({})", old_name, error));
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let applier = Applier {
+            options: &self.options,
+            review: &self.review,
+        };
+        applier.apply(named_candidates)?;
+
+        Ok(())
+    }
+}
+
+/// Records the identity of every file to rename. Renames whose target is taken by a file
+/// that stays in place or by another rename become conflicts.
+fn create_plan(
+    directory: PathBuf,
+    allow_escape: bool,
+    renames: Vec<(Candidate, String)>,
+    mut conflicts: Vec<Conflict>,
+) -> Result<Plan, RenamerError> {
+    let sources: HashSet<_> = renames
+        .iter()
+        .map(|(candidate, _)| candidate.name.clone())
+        .collect();
+    let mut targets = HashSet::new();
+    let mut planned_renames = Vec::with_capacity(renames.len());
+
+    for (candidate, new_name) in renames {
+        let is_taken = !targets.insert(new_name.clone())
+            || (!sources.contains(&new_name) && symlink_metadata(&new_name).is_ok());
+
+        if is_taken {
+            conflicts.push(Conflict {
+                source: candidate.name,
+                reason: RenamerError::TargetTaken(new_name.clone()).to_string(),
+                target: Some(new_name),
+            });
+            continue;
+        }
+
+        let metadata = symlink_metadata(&candidate.path).map_err(RenamerError::IoError)?;
+        planned_renames.push(PlannedRename {
+            source: candidate.name,
+            target: new_name,
+            capture_groups: candidate.capture_groups,
+            identity: FileIdentity::from(&metadata),
+        });
+    }
+
+    Ok(Plan {
+        directory,
+        allow_escape,
+        renames: planned_renames,
+        conflicts,
+    })
+}
+
+/// Renames the files relative to the root directory, moving files out of the way first.
+fn execute_renames(
+    renames: Vec<Rename>,
+    root: &Path,
+    options: &RenamerOptions,
+) -> Result<(), RenamerError> {
+    if let Some(flavor) = options.emit_script {
+        options
+            .console
+            .write_output(&create_script(&plan_steps(renames), flavor));
+        return Ok(());
+    }
+
+    let repository = if options.git {
+        Some(Repository::open(root).map_err(RenamerError::GitError)?)
+    } else {
+        None
+    };
+
+    let renames = match &repository {
+        Some(repository) => renames
+            .into_iter()
+            .filter(|rename| match repository.check(rename, options.force) {
+                Ok(()) => true,
+                Err(reason) => {
+                    options
+                        .console
+                        .report(&format!("Ignoring file: {:?} ({})", rename.source, reason));
+                    false
+                }
+            })
+            .collect(),
+        None => renames,
+    };
+    let steps = plan_steps(renames);
+    let mut performed_renames = Vec::new();
+    let mut result = Ok(());
+
+    let executor = if options.dry_run {
+        None
+    } else {
+        Some(Executor::open(root, options.allow_escape).map_err(RenamerError::IoError)?)
+    };
+
+    'steps: for step in steps {
+        for rename in step.renames() {
+            options.console.write_output(&if options.null_separated {
+                format!("{}\0{}\0", rename.source.display(), rename.target.display())
+            } else {
+                format!("Renaming {:?} → {:?}\n", rename.source, rename.target)
+            });
+        }
+
+        if let Some(executor) = &executor {
+            match executor.execute(&step) {
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                    options.console.report("Path already exists. Skipping...");
+                }
+                Err(error) => {
+                    result = Err(RenamerError::IoError(error));
+                    break 'steps;
+                }
+                Ok(()) => performed_renames.extend_from_slice(step.renames()),
             }
+        }
+    }
 
-            create_dir_all(new_path.parent().unwrap()).map_err(RenamerError::IoError)?;
+    // The renames before a failure have been performed, so the index must follow them anyway
+    let recorded = match &repository {
+        Some(repository) if !options.dry_run => repository
+            .record_renames(&performed_renames)
+            .map_err(RenamerError::GitError),
+        _ => Ok(()),
+    };
 
-            rename(old_path, new_path).map_err(RenamerError::IoError)?;
+    match (result, recorded) {
+        (Err(error), Err(git_error)) => {
+            options.console.report(&git_error.to_string());
+            Err(error)
         }
+        (result, recorded) => result.and(recorded),
+    }
+}
+
+/// Executes a plan after making sure that none of its files have changed.
+pub(crate) fn apply_plan(plan: &Plan, options: &RenamerOptions) -> Result<(), Box<dyn Error>> {
+    set_current_dir(&plan.directory).map_err(RenamerError::IoError)?;
+    plan.verify()?;
 
+    let options = RenamerOptions {
+        allow_escape: plan.allow_escape,
+        ..options.clone()
+    };
+    check_targets(plan, &options)?;
+
+    let renames = plan
+        .renames
+        .iter()
+        .map(|rename| Rename {
+            source: PathBuf::from(&rename.source),
+            target: PathBuf::from(&rename.target),
+        })
+        .collect();
+    execute_renames(renames, &plan.directory, &options)?;
+
+    Ok(())
+}
+
+/// Validates the targets like planning does, as the plan may have been edited since.
+fn check_targets(plan: &Plan, options: &RenamerOptions) -> Result<(), Box<dyn Error>> {
+    let applier = Applier {
+        options,
+        review: &Review::default(),
+    };
+    let traversal_guard = if plan.allow_escape {
+        None
+    } else {
+        Some(TraversalGuard::new(&plan.directory).map_err(RenamerError::IoError)?)
+    };
+    let mut limit_checker = LimitChecker::new(plan.directory.clone(), false);
+
+    let problems: Vec<_> = plan
+        .renames
+        .iter()
+        .filter_map(|rename| {
+            match applier.validate_new_name(
+                Ok(rename.target.clone()),
+                traversal_guard.as_ref(),
+                &mut limit_checker,
+            ) {
+                Ok(target) if target == rename.target => None,
+                Ok(target) => Some(format!("{:?} would become {:?}", rename.target, target)),
+                Err(error) => Some(format!("{:?}: {}", rename.target, error)),
+            }
+        })
+        .collect();
+
+    if problems.is_empty() {
         Ok(())
+    } else {
+        Err(Box::new(PlanError::InvalidTargets(problems)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::confirmation::PrompterMock;
+    use crate::matcher::MatcherImpl;
+    use crate::matching_pattern::{self, Parser as _};
+    use crate::metadata_filter::FileType;
+    use crate::name_generator::NameGeneratorImpl;
+    use crate::plan_editor::PlanEditorMock;
+    use crate::replacement_pattern::{self, Parser as _};
+    use std::cell::RefCell;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::sync::{Mutex, PoisonError};
+
+    /// Renamers change the current directory, so tests that run them must not overlap.
+    static CURRENT_DIRECTORY: Mutex<()> = Mutex::new(());
+
+    #[derive(Debug, Default)]
+    struct RecordingConsole {
+        output: RefCell<String>,
+        diagnostics: RefCell<String>,
+    }
+
+    impl Console for RecordingConsole {
+        fn write_output(&self, text: &str) {
+            self.output.borrow_mut().push_str(text);
+        }
+
+        fn report(&self, message: &str) {
+            self.diagnostics
+                .borrow_mut()
+                .push_str(&format!("{}\n", message));
+        }
+    }
+
+    fn matcher(pattern: &str) -> Box<dyn Matcher> {
+        let pattern = matching_pattern::ParserImpl::new().parse(pattern).unwrap();
+
+        Box::new(MatcherImpl::new(pattern))
+    }
+
+    fn renamer(
+        (matching_pattern, replacement_pattern): (&str, &str),
+        exclusion_patterns: &[&str],
+        options: RenamerOptions,
+    ) -> RenamerImpl {
+        let glob = matching_pattern::ParserImpl::new()
+            .parse(matching_pattern)
+            .unwrap()
+            .as_glob();
+        let replacement_pattern = replacement_pattern::ParserImpl::new()
+            .parse(replacement_pattern)
+            .unwrap();
+        let exclusions = exclusion_patterns
+            .iter()
+            .map(|pattern| Exclusion::new(String::from(*pattern), matcher(pattern)))
+            .collect();
+
+        RenamerImpl::new(
+            CandidateSource::Glob(glob),
+            matcher(matching_pattern),
+            exclusions,
+            None,
+            Box::new(NameGeneratorImpl::new(replacement_pattern)),
+            options,
+            Review::default(),
+        )
+    }
+
+    fn create_directory(name: &str, files: &[&str]) -> PathBuf {
+        let directory = temp_dir().join(format!("mvr-renamer-{}-{}", name, std::process::id()));
+        create_dir_all(&directory).unwrap();
+
+        for file in files {
+            write(directory.join(file), file).unwrap();
+        }
+
+        directory
+    }
+
+    fn candidate(name: &str) -> Candidate {
+        Candidate {
+            path: PathBuf::from(name),
+            name: String::from(name),
+            properties: FileProperties {
+                file_type: FileType::File,
+                size: 0,
+                modified: None,
+                changed: None,
+                owner: 0,
+                executable: false,
+            },
+            capture_groups: Vec::new(),
+        }
+    }
+
+    fn renames(pairs: &[(&str, &str)]) -> Vec<(Candidate, String)> {
+        pairs
+            .iter()
+            .map(|(old_name, new_name)| (candidate(old_name), String::from(*new_name)))
+            .collect()
+    }
+
+    fn names(renames: &[(Candidate, String)]) -> Vec<(&str, &str)> {
+        renames
+            .iter()
+            .map(|(candidate, new_name)| (candidate.name.as_str(), new_name.as_str()))
+            .collect()
+    }
+
+    fn confirm_renames(
+        prompter: &PrompterMock,
+        pairs: &[(&str, &str)],
+    ) -> Result<Vec<(Candidate, String)>, RenamerError> {
+        let applier = Applier {
+            options: &RenamerOptions::default(),
+            review: &Review::default(),
+        };
+        let mut limit_checker = LimitChecker::new(temp_dir(), false);
+
+        applier.confirm_renames(prompter, renames(pairs), None, &mut limit_checker)
+    }
+
+    fn edit_renames(
+        edited_plan: &str,
+        pairs: &[(&str, &str)],
+        console: &Rc<RecordingConsole>,
+    ) -> Result<Vec<(Candidate, String)>, RenamerError> {
+        let mut editor = PlanEditorMock::new();
+        editor
+            .expect_edit(|arg| arg.any())
+            .returns_once(Ok(String::from(edited_plan)));
+        let options = RenamerOptions {
+            console: console.clone(),
+            ..RenamerOptions::default()
+        };
+        let applier = Applier {
+            options: &options,
+            review: &Review::default(),
+        };
+        let mut limit_checker = LimitChecker::new(temp_dir(), false);
+
+        applier.edit_renames(&editor, renames(pairs), None, &mut limit_checker)
+    }
+
+    #[test]
+    fn skips_files_whose_lines_were_deleted() {
+        let console = Rc::new(RecordingConsole::default());
+
+        let edited = edit_renames("# a\n1\tx\n", &[("a", "b"), ("c", "d")], &console).unwrap();
+
+        assert_eq!(vec![("a", "x")], names(&edited));
+    }
+
+    #[test]
+    fn skips_and_reports_files_edited_to_a_taken_name() {
+        let console = Rc::new(RecordingConsole::default());
+
+        let edited = edit_renames("1\td\n2\td\n", &[("a", "b"), ("c", "d")], &console).unwrap();
+
+        assert_eq!(vec![("c", "d")], names(&edited));
+        assert_eq!(
+            "Ignoring file: \"a\" (\"d\" is already taken)\n",
+            *console.diagnostics.borrow()
+        );
+    }
+
+    #[test]
+    fn fails_when_lines_were_added() {
+        let console = Rc::new(RecordingConsole::default());
+
+        let result = edit_renames("1\tb\n2\td\n3\tf\n", &[("a", "b"), ("c", "d")], &console);
+
+        assert!(matches!(
+            result,
+            Err(RenamerError::EditError(EditError::UnknownEntry(3)))
+        ));
+    }
+
+    #[test]
+    fn renames_remaining_files_without_asking_after_all() {
+        let mut prompter = PrompterMock::new();
+        prompter.expect_confirm_calls_in_order();
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("a"), |arg| arg.partial_eq("b"))
+            .returns_once(Ok(Answer::No));
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("c"), |arg| arg.partial_eq("d"))
+            .returns_once(Ok(Answer::All));
+
+        let confirmed = confirm_renames(&prompter, &[("a", "b"), ("c", "d"), ("e", "f")]).unwrap();
+
+        assert_eq!(vec![("c", "d"), ("e", "f")], names(&confirmed));
+    }
+
+    #[test]
+    fn skips_remaining_files_after_quit() {
+        let mut prompter = PrompterMock::new();
+        prompter.expect_confirm_calls_in_order();
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("a"), |arg| arg.partial_eq("b"))
+            .returns_once(Ok(Answer::Yes));
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("c"), |arg| arg.partial_eq("d"))
+            .returns_once(Ok(Answer::Quit));
+
+        let confirmed = confirm_renames(&prompter, &[("a", "b"), ("c", "d"), ("e", "f")]).unwrap();
+
+        assert_eq!(vec![("a", "b")], names(&confirmed));
+    }
+
+    #[test]
+    fn asks_again_after_editing_to_a_taken_name() {
+        let mut prompter = PrompterMock::new();
+        prompter.expect_confirm_calls_in_order();
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("a"), |arg| arg.partial_eq("b"))
+            .returns_once(Ok(Answer::Edit(String::from("d"))));
+        prompter
+            .expect_reject(|arg| arg.partial_eq("\"d\" is already taken"))
+            .returns(());
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("a"), |arg| arg.partial_eq("b"))
+            .returns_once(Ok(Answer::Yes));
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("c"), |arg| arg.partial_eq("d"))
+            .returns_once(Ok(Answer::Yes));
+
+        let confirmed = confirm_renames(&prompter, &[("a", "b"), ("c", "d")]).unwrap();
+
+        assert_eq!(vec![("a", "b"), ("c", "d")], names(&confirmed));
+    }
+
+    #[test]
+    fn keeps_the_names_of_declined_files_taken() {
+        let _lock = CURRENT_DIRECTORY
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let directory = create_directory("declined", &["a", "c", "x"]);
+        set_current_dir(&directory).unwrap();
+
+        let mut prompter = PrompterMock::new();
+        prompter.expect_confirm_calls_in_order();
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("a"), |arg| arg.partial_eq("b"))
+            .returns_once(Ok(Answer::No));
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("x"), |arg| arg.partial_eq("y"))
+            .returns_once(Ok(Answer::Yes));
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("c"), |arg| arg.partial_eq("d"))
+            .returns_once(Ok(Answer::Edit(String::from("a"))));
+        prompter
+            .expect_reject(|arg| arg.partial_eq("\"a\" is already taken"))
+            .returns(());
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("c"), |arg| arg.partial_eq("d"))
+            .returns_once(Ok(Answer::Edit(String::from("x"))));
+        prompter
+            .expect_confirm(|arg| arg.partial_eq("c"), |arg| arg.partial_eq("x"))
+            .returns_once(Ok(Answer::Yes));
+
+        let confirmed = confirm_renames(&prompter, &[("a", "b"), ("x", "y"), ("c", "d")]);
+        remove_dir_all(&directory).unwrap();
+
+        assert_eq!(vec![("x", "y"), ("c", "x")], names(&confirmed.unwrap()));
+    }
+
+    #[test]
+    fn skips_and_reports_excluded_files() {
+        let _lock = CURRENT_DIRECTORY
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let directory = create_directory("exclusions", &["a.txt", "README.txt"]);
+        let console = Rc::new(RecordingConsole::default());
+        let options = RenamerOptions {
+            console: console.clone(),
+            ..RenamerOptions::default()
+        };
+
+        let result = renamer(("(*).txt", "$0.md"), &["README*"], options)
+            .rename_files_in_directory(&directory);
+        let files = (
+            directory.join("a.md").exists(),
+            directory.join("README.txt").exists(),
+        );
+        remove_dir_all(&directory).unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!((true, true), files);
+        assert_eq!(
+            "Excluding \"README.txt\" (matches \"README*\")\n",
+            *console.diagnostics.borrow()
+        );
+        assert!(!console.output.borrow().contains("README"));
+    }
+
+    #[test]
+    fn writes_only_the_plan_to_the_output() {
+        let _lock = CURRENT_DIRECTORY
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let directory = create_directory("plan", &["a.txt", "README.txt"]);
+        let console = Rc::new(RecordingConsole::default());
+        let options = RenamerOptions {
+            plan_only: true,
+            console: console.clone(),
+            ..RenamerOptions::default()
+        };
+
+        let result = renamer(("(*).txt", "$0.md"), &["README*"], options)
+            .rename_files_in_directory(&directory);
+        let is_unchanged = directory.join("a.txt").exists();
+        remove_dir_all(&directory).unwrap();
+
+        assert!(result.is_ok());
+        assert!(is_unchanged);
+        let plan = Plan::from_json(&console.output.borrow()).unwrap();
+        let renames: Vec<_> = plan
+            .renames
+            .iter()
+            .map(|rename| (rename.source.as_str(), rename.target.as_str()))
+            .collect();
+        assert_eq!(vec![("a.txt", "a.md")], renames);
+        assert!(console.diagnostics.borrow().starts_with("Excluding"));
+    }
+
+    #[test]
+    fn rejects_plans_with_tampered_targets() {
+        let _lock = CURRENT_DIRECTORY
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let directory = create_directory("tampered", &["a.txt", "b.txt"]);
+        let console = Rc::new(RecordingConsole::default());
+        let options = RenamerOptions {
+            plan_only: true,
+            console: console.clone(),
+            ..RenamerOptions::default()
+        };
+        renamer(("(*).txt", "$0.md"), &[], options.clone())
+            .rename_files_in_directory(&directory)
+            .unwrap();
+        let plan = Plan::from_json(&console.output.borrow()).unwrap();
+
+        let mut escaping = plan.clone();
+        escaping.renames[0].target = String::from("../escaped");
+        let escaping_result = apply_plan(&escaping, &options);
+        let mut absolute = plan.clone();
+        absolute.renames[0].target = directory.join("../absolute").display().to_string();
+        let absolute_result = apply_plan(&absolute, &options);
+        let unchanged = directory.join("a.txt").exists() && directory.join("b.txt").exists();
+        let applied_result = apply_plan(&plan, &options);
+        let applied = directory.join("a.md").exists() && directory.join("b.md").exists();
+        remove_dir_all(&directory).unwrap();
+
+        for result in &[escaping_result, absolute_result] {
+            let error = result.as_ref().unwrap_err().to_string();
+            assert!(error.starts_with("The plan contains invalid targets"));
+        }
+        assert!(unchanged);
+        assert!(applied_result.is_ok());
+        assert!(applied);
+    }
+
+    #[test]
+    fn writes_only_the_script_to_the_output() {
+        let _lock = CURRENT_DIRECTORY
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let directory = create_directory("script", &["a.txt", "README.txt"]);
+        let console = Rc::new(RecordingConsole::default());
+        let options = RenamerOptions {
+            emit_script: Some(ScriptFlavor::Shell),
+            console: console.clone(),
+            ..RenamerOptions::default()
+        };
+
+        let result = renamer(("(*).txt", "$0.md"), &["README*"], options)
+            .rename_files_in_directory(&directory);
+        let is_unchanged = directory.join("a.txt").exists();
+        remove_dir_all(&directory).unwrap();
+
+        assert!(result.is_ok());
+        assert!(is_unchanged);
+        assert!(console.output.borrow().starts_with("#!/bin/sh\n"));
+        assert!(!console.output.borrow().contains("README"));
+        assert!(console.diagnostics.borrow().starts_with("Excluding"));
     }
 }
@@ -1,19 +1,134 @@
+use crate::audio_tags::AudioTag;
+use crate::case_conversion::Case;
+use crate::content_hash::HashAlgorithm;
+use crate::image_metadata::ExifTag;
+use chrono::format::{Item, StrftimeItems};
 use nom::branch::alt;
-use nom::bytes::complete::take_while1;
-use nom::character::complete::{char as nom_char, digit1};
-use nom::combinator::map;
-use nom::multi::many1;
-use nom::sequence::preceded;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{alphanumeric1, char as nom_char, digit1};
+use nom::combinator::{cut, map, map_opt, map_res, not, opt, peek, recognize, value, verify};
+use nom::error::ErrorKind;
+use nom::multi::{many0, many1, separated_nonempty_list};
+use nom::sequence::{delimited, pair, preceded, tuple};
+use nom::IResult;
 use std::error::Error;
 use std::fmt::{self, Debug, Display};
 
 #[cfg(test)]
 use mockiato::mockable;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Filter {
+    Case(Case),
+    /// Pads a number with leading zeros to the given width
+    Pad(usize),
+    /// Strips leading zeros from a number
+    Integer,
+    Add(i64),
+    Multiply(i64),
+    /// Takes a substring by character offset and length. Negative values count from the end.
+    Slice {
+        offset: i64,
+        length: Option<i64>,
+    },
+    Replace {
+        pattern: String,
+        replacement: String,
+        all: bool,
+    },
+    Trim,
+    /// Shortens the value to at most the given number of characters
+    Truncate(usize),
+    /// Transliterates the value to ASCII
+    Ascii,
+    /// Converts the value to lowercase ASCII words separated by hyphens
+    Slug,
+    /// Applies the Unicode normalization form C (composed)
+    Nfc,
+    /// Applies the Unicode normalization form D (decomposed)
+    Nfd,
+    /// Parses the value with the first matching input format and formats it with the output format
+    Date {
+        input_formats: Vec<String>,
+        output_format: String,
+    },
+    /// Used instead of the value if it is empty or missing
+    Default(Vec<Element>),
+    /// Used instead of the value if it is not empty
+    Conditional(Vec<Element>),
+}
+
+/// Determines which files share a counter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Scope {
+    Global,
+    /// Restarts the counter for each parent directory
+    Directory,
+    /// Restarts the counter for each distinct value
+    Value(Box<Element>),
+}
+
+/// Numbers files in the order in which they are renamed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Counter {
+    pub(crate) start: i64,
+    pub(crate) step: i64,
+    pub(crate) width: usize,
+    pub(crate) scope: Scope,
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Self {
+            start: 1,
+            step: 1,
+            width: 0,
+            scope: Scope::Global,
+        }
+    }
+}
+
+/// Information about the file that does not come from the matching pattern.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Variable {
+    /// The modification time, formatted with the given `strftime` format
+    ModificationTime(String),
+    /// The time of the last status change (`st_ctime`), formatted with the given `strftime` format
+    ChangeTime(String),
+    Size {
+        human_readable: bool,
+    },
+    /// The file name without its extension
+    Stem,
+    /// The extension without the leading dot, including compound extensions such as `tar.gz`
+    Extension,
+    /// The name of the n-th directory above the file, where 1 is the parent directory
+    Directory(usize),
+    /// An EXIF tag, dates are formatted with the given `strftime` format
+    Exif {
+        tag: ExifTag,
+        format: Option<String>,
+    },
+    /// A tag of an audio file, e.g. the artist
+    AudioTag(AudioTag),
+    /// The width of an image in pixels
+    Width,
+    /// The height of an image in pixels
+    Height,
+    /// The hexadecimal digest of the file contents, optionally shortened to the given length
+    ContentHash {
+        algorithm: HashAlgorithm,
+        length: Option<usize>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) enum Element {
     Text(String),
     CaptureGroup(usize),
+    Counter(Counter),
+    Variable(Variable),
+    Filtered(Box<Element>, Vec<Filter>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -54,20 +169,7 @@ impl ParserImpl {
 
 impl Parser for ParserImpl {
     fn parse(&self, input: &str) -> Result<Pattern, ParsingError> {
-        let text = map(take_while1::<_, _, ()>(|c| c != '$'), |input| {
-            Element::Text(String::from(input))
-        });
-
-        let capture_group = preceded(
-            nom_char('$'),
-            map(digit1, |input: &str| {
-                Element::CaptureGroup(input.parse().unwrap())
-            }),
-        );
-
-        let elements = many1(alt((capture_group, text)));
-
-        let pattern = match elements(input).map_err(|_| ParsingError::InvalidSyntax)? {
+        let pattern = match many1(element)(input).map_err(|_| ParsingError::InvalidSyntax)? {
             (remaining_text, _) if !remaining_text.is_empty() => {
                 return Err(ParsingError::InvalidSyntax)
             }
@@ -78,6 +180,314 @@ impl Parser for ParserImpl {
     }
 }
 
+fn element(input: &str) -> IResult<&str, Element> {
+    let text = map(take_while1(|c| c != '$'), |input| {
+        Element::Text(String::from(input))
+    });
+
+    alt((braced_element, capture_group, text))(input)
+}
+
+/// Parses elements inside of braces, e.g. the default value in `${2:-untitled}`.
+fn nested_elements(input: &str) -> IResult<&str, Vec<Element>> {
+    let text = map(take_while1(|c| c != '$' && c != '}'), |input| {
+        Element::Text(String::from(input))
+    });
+
+    many0(alt((braced_element, capture_group, text)))(input)
+}
+
+fn capture_group(input: &str) -> IResult<&str, Element> {
+    preceded(
+        nom_char('$'),
+        map(capture_group_index, Element::CaptureGroup),
+    )(input)
+}
+
+fn braced_element(input: &str) -> IResult<&str, Element> {
+    let source = alt((
+        map(variable, Element::Variable),
+        counter,
+        map(capture_group_index, Element::CaptureGroup),
+    ));
+
+    let modifier = alt((
+        preceded(nom_char(':'), filter),
+        map(preceded(nom_char('+'), number), Filter::Add),
+        map(preceded(nom_char('-'), number), |number| {
+            Filter::Add(-number)
+        }),
+        map(preceded(nom_char('*'), number), Filter::Multiply),
+        replace,
+    ));
+
+    delimited(
+        tag("${"),
+        map(pair(source, many0(modifier)), |(element, filters)| {
+            if filters.is_empty() {
+                element
+            } else {
+                Element::Filtered(Box::new(element), filters)
+            }
+        }),
+        nom_char('}'),
+    )(input)
+}
+
+fn variable(input: &str) -> IResult<&str, Variable> {
+    alt((
+        map(
+            preceded(tag("mtime"), time_format),
+            Variable::ModificationTime,
+        ),
+        map(preceded(tag("ctime"), time_format), Variable::ChangeTime),
+        exif_variable,
+        map(
+            preceded(tag("tag:"), map_opt(alphanumeric1, AudioTag::from_name)),
+            Variable::AudioTag,
+        ),
+        value(Variable::Width, tag("width")),
+        value(Variable::Height, tag("height")),
+        value(
+            Variable::Size {
+                human_readable: true,
+            },
+            tag("size:human"),
+        ),
+        value(
+            Variable::Size {
+                human_readable: false,
+            },
+            tag("size"),
+        ),
+        value(Variable::Stem, tag("stem")),
+        value(Variable::Extension, tag("ext")),
+        value(Variable::Directory(1), tag("parent")),
+        map(preceded(tag("dir:"), number), |level| {
+            Variable::Directory(level as usize)
+        }),
+        map(
+            pair(
+                alt((
+                    value(HashAlgorithm::Sha256, tag("sha256")),
+                    value(HashAlgorithm::Sha1, tag("sha1")),
+                    value(HashAlgorithm::Blake3, tag("blake3")),
+                    value(HashAlgorithm::Crc32, tag("crc32")),
+                )),
+                opt(preceded(nom_char(':'), number)),
+            ),
+            |(algorithm, length)| Variable::ContentHash {
+                algorithm,
+                length: length.map(|length| length as usize),
+            },
+        ),
+    ))(input)
+}
+
+fn exif_variable(input: &str) -> IResult<&str, Variable> {
+    let (input, tag) = preceded(tag("exif:"), map_opt(alphanumeric1, ExifTag::from_name))(input)?;
+
+    if tag.is_date() {
+        map(time_format, |format| Variable::Exif {
+            tag,
+            format: Some(format),
+        })(input)
+    } else {
+        Ok((input, Variable::Exif { tag, format: None }))
+    }
+}
+
+/// Formats may contain colons, so they extend to the closing brace
+/// or to a default value or conditional segment (`:-` or `:+`).
+fn time_format(input: &str) -> IResult<&str, String> {
+    map(
+        opt(preceded(
+            pair(not(alt((tag(":-"), tag(":+")))), nom_char(':')),
+            verify(time_format_text, is_valid_time_format),
+        )),
+        |format: Option<&str>| String::from(format.unwrap_or(DEFAULT_TIME_FORMAT)),
+    )(input)
+}
+
+fn time_format_text(input: &str) -> IResult<&str, &str> {
+    let end = input.find('}').unwrap_or(input.len());
+    let end = [":-", ":+"]
+        .iter()
+        .filter_map(|separator| input[..end].find(separator))
+        .min()
+        .unwrap_or(end);
+
+    if end == 0 {
+        Err(nom::Err::Error((input, ErrorKind::TakeWhile1)))
+    } else {
+        Ok((&input[end..], &input[..end]))
+    }
+}
+
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d";
+
+fn is_valid_time_format(format: &str) -> bool {
+    StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
+fn counter(input: &str) -> IResult<&str, Element> {
+    let scope = alt((
+        value(Scope::Directory, tag("dir")),
+        map(alt((braced_element, capture_group)), |element| {
+            Scope::Value(Box::new(element))
+        }),
+    ));
+
+    let setting = alt((
+        map(preceded(tag("per="), scope), CounterSetting::Scope),
+        map(
+            preceded(tag("start="), signed_number),
+            CounterSetting::Start,
+        ),
+        map(preceded(tag("step="), signed_number), CounterSetting::Step),
+        map(
+            preceded(
+                tag("width="),
+                verify(map_res(digit1, str::parse), |width| *width <= MAX_PAD_WIDTH),
+            ),
+            CounterSetting::Width,
+        ),
+    ));
+
+    let settings = map(
+        separated_nonempty_list(nom_char(','), setting),
+        |settings| {
+            settings
+                .into_iter()
+                .fold(Counter::default(), |counter, setting| match setting {
+                    CounterSetting::Start(start) => Counter { start, ..counter },
+                    CounterSetting::Step(step) => Counter { step, ..counter },
+                    CounterSetting::Width(width) => Counter { width, ..counter },
+                    CounterSetting::Scope(scope) => Counter { scope, ..counter },
+                })
+        },
+    );
+
+    map(
+        preceded(nom_char('n'), opt(preceded(nom_char(':'), settings))),
+        |counter| Element::Counter(counter.unwrap_or_default()),
+    )(input)
+}
+
+enum CounterSetting {
+    Start(i64),
+    Step(i64),
+    Width(usize),
+    Scope(Scope),
+}
+
+fn replace(input: &str) -> IResult<&str, Filter> {
+    map(
+        tuple((
+            nom_char('/'),
+            opt(nom_char('/')),
+            take_while1(|c| c != '/' && c != '}'),
+            opt(preceded(
+                nom_char('/'),
+                take_while(|c| c != ':' && c != '}'),
+            )),
+        )),
+        |(_, all, pattern, replacement): (_, _, &str, Option<&str>)| Filter::Replace {
+            pattern: String::from(pattern),
+            replacement: String::from(replacement.unwrap_or_default()),
+            all: all.is_some(),
+        },
+    )(input)
+}
+
+fn filter(input: &str) -> IResult<&str, Filter> {
+    // Negative offsets need a leading space to distinguish them from other filters
+    let offset = alt((number, preceded(nom_char(' '), signed_number)));
+
+    alt((
+        map(preceded(nom_char('-'), nested_elements), Filter::Default),
+        map(
+            preceded(nom_char('+'), nested_elements),
+            Filter::Conditional,
+        ),
+        map(pad_width, Filter::Pad),
+        map(
+            pair(offset, opt(preceded(nom_char(':'), signed_number))),
+            |(offset, length)| Filter::Slice { offset, length },
+        ),
+        value(Filter::Integer, tag("int")),
+        value(Filter::Trim, tag("trim")),
+        map(delimited(tag("trunc("), number, nom_char(')')), |length| {
+            Filter::Truncate(length as usize)
+        }),
+        date,
+        value(Filter::Ascii, tag("ascii")),
+        value(Filter::Slug, tag("slug")),
+        value(Filter::Nfc, tag("nfc")),
+        value(Filter::Nfd, tag("nfd")),
+        value(Filter::Case(Case::Upper), tag("upper")),
+        value(Filter::Case(Case::Lower), tag("lower")),
+        value(Filter::Case(Case::Title), tag("title")),
+        value(Filter::Case(Case::Snake), tag("snake")),
+        value(Filter::Case(Case::Kebab), tag("kebab")),
+        value(Filter::Case(Case::Camel), tag("camel")),
+        value(Filter::Case(Case::Pascal), tag("pascal")),
+    ))(input)
+}
+
+/// Longer numbers would not fit into a file name.
+const MAX_PAD_WIDTH: usize = 255;
+
+/// Parses widths like `03`. Once a digit follows the zero, the width must be valid,
+/// so that a width that is too large is not parsed as an offset instead.
+fn pad_width(input: &str) -> IResult<&str, usize> {
+    preceded(
+        pair(nom_char('0'), peek(digit1)),
+        cut(verify(map_res(digit1, str::parse), |width| {
+            *width <= MAX_PAD_WIDTH
+        })),
+    )(input)
+}
+
+fn date(input: &str) -> IResult<&str, Filter> {
+    let format = || {
+        map(
+            verify(
+                map(take_while1(|c| !"|=)".contains(c)), str::trim),
+                |format: &str| !format.is_empty() && is_valid_time_format(format),
+            ),
+            String::from,
+        )
+    };
+
+    map(
+        delimited(
+            tag("date("),
+            pair(
+                separated_nonempty_list(nom_char('|'), format()),
+                preceded(tag("=>"), format()),
+            ),
+            nom_char(')'),
+        ),
+        |(input_formats, output_format)| Filter::Date {
+            input_formats,
+            output_format,
+        },
+    )(input)
+}
+
+fn capture_group_index(input: &str) -> IResult<&str, usize> {
+    map_res(digit1, str::parse)(input)
+}
+
+fn number(input: &str) -> IResult<&str, i64> {
+    map_res(digit1, str::parse)(input)
+}
+
+fn signed_number(input: &str) -> IResult<&str, i64> {
+    map_res(recognize(pair(opt(nom_char('-')), digit1)), str::parse)(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,4 +529,445 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn parse_with_braced_group() {
+        let expected = Pattern {
+            elements: vec![Element::CaptureGroup(0), Element::Text(String::from("1"))],
+        };
+
+        let actual = ParserImpl::new().parse("${0}1").unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_filters() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(0)),
+                    vec![Filter::Case(Case::Kebab)],
+                ),
+                Element::Text(String::from(".")),
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(1)),
+                    vec![Filter::Case(Case::Title), Filter::Case(Case::Lower)],
+                ),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${0:kebab}.${1:title:lower}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn fails_with_unknown_filter() {
+        let actual = ParserImpl::new().parse("${0:shout}");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn fails_with_unclosed_brace() {
+        let actual = ParserImpl::new().parse("${0:upper");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn fails_with_out_of_range_numbers() {
+        for pattern in &[
+            "$99999999999999999999",
+            "${1+99999999999999999999}",
+            "${0:trunc(99999999999999999999)}",
+            "${1:0999999}",
+            "${1:0256}",
+            "${0: -99999999999999999999}",
+            "${n:start=99999999999999999999}",
+            "${n:width=256}",
+        ] {
+            assert_eq!(
+                Err(ParsingError::InvalidSyntax),
+                ParserImpl::new().parse(pattern),
+                "{}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn parse_with_numeric_filters() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Filtered(Box::new(Element::CaptureGroup(0)), vec![Filter::Pad(3)]),
+                Element::Filtered(Box::new(Element::CaptureGroup(1)), vec![Filter::Integer]),
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(2)),
+                    vec![Filter::Add(100), Filter::Multiply(2), Filter::Add(-1)],
+                ),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${0:03}${1:int}${2+100*2-1}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_counter() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Counter(Counter::default()),
+                Element::Text(String::from("_")),
+                Element::Counter(Counter {
+                    start: 0,
+                    step: -2,
+                    width: 4,
+                    scope: Scope::Global,
+                }),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${n}_${n:start=0,step=-2,width=4}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_filtered_counter() {
+        let expected = Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::Counter(Counter::default())),
+                vec![Filter::Add(100), Filter::Pad(4)],
+            )],
+        };
+
+        let actual = ParserImpl::new().parse("${n+100:04}").unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn fails_with_unknown_counter_setting() {
+        let actual = ParserImpl::new().parse("${n:begin=1}");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn parse_with_scoped_counters() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Counter(Counter {
+                    scope: Scope::Value(Box::new(Element::CaptureGroup(0))),
+                    ..Counter::default()
+                }),
+                Element::Counter(Counter {
+                    width: 3,
+                    scope: Scope::Directory,
+                    ..Counter::default()
+                }),
+                Element::Counter(Counter {
+                    scope: Scope::Value(Box::new(Element::Filtered(
+                        Box::new(Element::CaptureGroup(1)),
+                        vec![Filter::Case(Case::Lower)],
+                    ))),
+                    ..Counter::default()
+                }),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${n:per=$0}${n:width=3,per=dir}${n:per=${1:lower}}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_slices() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(0)),
+                    vec![Filter::Slice {
+                        offset: 2,
+                        length: Some(4),
+                    }],
+                ),
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(0)),
+                    vec![Filter::Slice {
+                        offset: -3,
+                        length: None,
+                    }],
+                ),
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(0)),
+                    vec![
+                        Filter::Slice {
+                            offset: 0,
+                            length: Some(-1),
+                        },
+                        Filter::Pad(3),
+                    ],
+                ),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${0:2:4}${0: -3}${0:0:-1:03}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_replacements() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(0)),
+                    vec![Filter::Replace {
+                        pattern: String::from(" "),
+                        replacement: String::from("_"),
+                        all: false,
+                    }],
+                ),
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(1)),
+                    vec![
+                        Filter::Replace {
+                            pattern: String::from("-"),
+                            replacement: String::new(),
+                            all: true,
+                        },
+                        Filter::Case(Case::Upper),
+                    ],
+                ),
+            ],
+        };
+
+        let actual = ParserImpl::new().parse("${0/ /_}${1//-/:upper}").unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_trim_and_truncate() {
+        let expected = Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::CaptureGroup(0)),
+                vec![Filter::Trim, Filter::Truncate(40)],
+            )],
+        };
+
+        let actual = ParserImpl::new().parse("${0:trim:trunc(40)}").unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_default_and_conditional() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(2)),
+                    vec![Filter::Default(vec![Element::Text(String::from(
+                        "untitled",
+                    ))])],
+                ),
+                Element::Filtered(
+                    Box::new(Element::CaptureGroup(2)),
+                    vec![Filter::Conditional(vec![
+                        Element::Text(String::from("_")),
+                        Element::CaptureGroup(2),
+                        Element::Filtered(
+                            Box::new(Element::CaptureGroup(3)),
+                            vec![Filter::Default(vec![])],
+                        ),
+                    ])],
+                ),
+                Element::Text(String::from("}.txt")),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${2:-untitled}${2:+_$2${3:-}}}.txt")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_variables() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Variable(Variable::ModificationTime(String::from("%Y-%m-%d %H:%M"))),
+                Element::Variable(Variable::ChangeTime(String::from(DEFAULT_TIME_FORMAT))),
+                Element::Variable(Variable::Size {
+                    human_readable: true,
+                }),
+                Element::Filtered(
+                    Box::new(Element::Variable(Variable::Size {
+                        human_readable: false,
+                    })),
+                    vec![Filter::Pad(8)],
+                ),
+                Element::Variable(Variable::Stem),
+                Element::Variable(Variable::Extension),
+                Element::Variable(Variable::Directory(1)),
+                Element::Variable(Variable::Directory(3)),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${mtime:%Y-%m-%d %H:%M}${ctime}${size:human}${size:08}${stem}${ext}${parent}${dir:3}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn fails_with_invalid_time_format() {
+        let actual = ParserImpl::new().parse("${mtime:%Q}");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn parse_with_content_hashes() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Variable(Variable::ContentHash {
+                    algorithm: HashAlgorithm::Sha256,
+                    length: Some(2),
+                }),
+                Element::Text(String::from("/")),
+                Element::Filtered(
+                    Box::new(Element::Variable(Variable::ContentHash {
+                        algorithm: HashAlgorithm::Crc32,
+                        length: None,
+                    })),
+                    vec![Filter::Case(Case::Upper)],
+                ),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${sha256:2}/${crc32:upper}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_image_metadata() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Filtered(
+                    Box::new(Element::Variable(Variable::Exif {
+                        tag: ExifTag::DateTimeOriginal,
+                        format: Some(String::from("%Y%m%d_%H:%M")),
+                    })),
+                    vec![Filter::Default(vec![Element::Variable(
+                        Variable::ModificationTime(String::from("%Y%m%d_%H:%M")),
+                    )])],
+                ),
+                Element::Filtered(
+                    Box::new(Element::Variable(Variable::Exif {
+                        tag: ExifTag::Model,
+                        format: None,
+                    })),
+                    vec![Filter::Case(Case::Snake)],
+                ),
+                Element::Variable(Variable::Width),
+                Element::Text(String::from("x")),
+                Element::Variable(Variable::Height),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${exif:DateTimeOriginal:%Y%m%d_%H:%M:-${mtime:%Y%m%d_%H:%M}}${exif:Model:snake}${width}x${height}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn fails_with_unknown_exif_tag() {
+        let actual = ParserImpl::new().parse("${exif:Unknown}");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn parse_with_audio_tags() {
+        let expected = Pattern {
+            elements: vec![
+                Element::Variable(Variable::AudioTag(AudioTag::Artist)),
+                Element::Text(String::from("/")),
+                Element::Filtered(
+                    Box::new(Element::Variable(Variable::AudioTag(AudioTag::Track))),
+                    vec![Filter::Pad(2)],
+                ),
+                Element::Text(String::from(" ")),
+                Element::Variable(Variable::AudioTag(AudioTag::Title)),
+            ],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${tag:artist}/${tag:track:02} ${tag:title}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_with_date_filter() {
+        let expected = Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::CaptureGroup(1)),
+                vec![Filter::Date {
+                    input_formats: vec![String::from("%d%m%Y"), String::from("%b-%d-%Y")],
+                    output_format: String::from("%Y-%m-%d"),
+                }],
+            )],
+        };
+
+        let actual = ParserImpl::new()
+            .parse("${1:date(%d%m%Y | %b-%d-%Y => %Y-%m-%d)}")
+            .unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn fails_with_date_filter_without_output_format() {
+        let actual = ParserImpl::new().parse("${1:date(%d%m%Y)}");
+
+        assert_eq!(Err(ParsingError::InvalidSyntax), actual);
+    }
+
+    #[test]
+    fn parse_with_text_normalization_filters() {
+        let expected = Pattern {
+            elements: vec![Element::Filtered(
+                Box::new(Element::CaptureGroup(0)),
+                vec![Filter::Nfc, Filter::Ascii, Filter::Slug, Filter::Nfd],
+            )],
+        };
+
+        let actual = ParserImpl::new().parse("${0:nfc:ascii:slug:nfd}").unwrap();
+
+        assert_eq!(expected, actual);
+    }
 }
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+
+/// The file systems whose naming rules new names have to follow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Platform {
+    Posix,
+    Windows,
+    MacOs,
+    /// Valid on all of the other platforms
+    Portable,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum SanitizationError {
+    InvalidCharacter(char),
+    TrailingCharacter(char),
+    ReservedName(String),
+    InvalidReplacement(char, String),
+}
+
+impl Display for SanitizationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanitizationError::InvalidCharacter(c) => write!(f, "Invalid character: {:?}", c),
+            SanitizationError::TrailingCharacter(c) => {
+                write!(f, "Names must not end with {:?}", c)
+            }
+            SanitizationError::ReservedName(name) => write!(f, "Reserved name: {:?}", name),
+            SanitizationError::InvalidReplacement(c, replacement) => write!(
+                f,
+                "The replacement {:?} for {:?} is not valid itself",
+                replacement, c
+            ),
+        }
+    }
+}
+
+impl Error for SanitizationError {}
+
+/// Replacements for invalid characters, unlisted characters are replaced with `_`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Replacements {
+    characters: HashMap<char, String>,
+}
+
+const DEFAULT_REPLACEMENT: &str = "_";
+
+impl Replacements {
+    /// Parses a comma separated list of replacements like `:=-,?=`.
+    pub(crate) fn parse(input: &str) -> Result<Self, String> {
+        let characters = input
+            .split(',')
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let mut chars = entry.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), Some('=')) => Ok((c, chars.collect())),
+                    _ => Err(format!("Invalid replacement: {:?}", entry)),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { characters })
+    }
+
+    fn replace(&self, c: char) -> &str {
+        self.characters
+            .get(&c)
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_REPLACEMENT)
+    }
+}
+
+const RESERVED_WINDOWS_NAMES: [&str; 32] = [
+    "CON", "CONIN$", "CONOUT$", "PRN", "AUX", "NUL", "COM0", "COM1", "COM2", "COM3", "COM4",
+    "COM5", "COM6", "COM7", "COM8", "COM9", "COM¹", "COM²", "COM³", "LPT0", "LPT1", "LPT2", "LPT3",
+    "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+/// Makes sure that new names are valid on the target platform,
+/// either by rejecting invalid names or by rewriting them.
+#[derive(Clone, Debug)]
+pub(crate) struct Sanitizer {
+    platform: Platform,
+    replacements: Option<Replacements>,
+}
+
+impl Sanitizer {
+    /// Fails if a replacement contains characters that are invalid on the platform themselves.
+    pub(crate) fn new(
+        platform: Platform,
+        replacements: Option<Replacements>,
+    ) -> Result<Self, SanitizationError> {
+        let sanitizer = Self {
+            platform,
+            replacements: None,
+        };
+
+        if let Some(replacements) = &replacements {
+            for (c, replacement) in &replacements.characters {
+                if replacement
+                    .chars()
+                    .any(|c| c == '/' || sanitizer.is_invalid_character(c))
+                {
+                    return Err(SanitizationError::InvalidReplacement(
+                        *c,
+                        replacement.clone(),
+                    ));
+                }
+            }
+        }
+
+        Ok(Self {
+            replacements,
+            ..sanitizer
+        })
+    }
+
+    /// Checks every component of the (relative) path separately.
+    pub(crate) fn sanitize(&self, name: &str) -> Result<String, SanitizationError> {
+        name.split('/')
+            .map(|component| self.sanitize_component(component))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|components| components.join("/"))
+    }
+
+    fn sanitize_component(&self, component: &str) -> Result<String, SanitizationError> {
+        if component == "." || component == ".." {
+            return Ok(String::from(component));
+        }
+
+        let mut sanitized = String::with_capacity(component.len());
+        for c in component.chars() {
+            match &self.replacements {
+                _ if !self.is_invalid_character(c) => sanitized.push(c),
+                Some(replacements) => sanitized.push_str(replacements.replace(c)),
+                None => return Err(SanitizationError::InvalidCharacter(c)),
+            }
+        }
+
+        // Replacements must not turn a name into a reference to a directory
+        if sanitized.is_empty() || sanitized == "." || sanitized == ".." {
+            sanitized = String::from(DEFAULT_REPLACEMENT);
+        }
+
+        if self.forbids_windows_names() {
+            if let Some(c) = sanitized.chars().last().filter(|c| *c == '.' || *c == ' ') {
+                if self.replacements.is_none() {
+                    return Err(SanitizationError::TrailingCharacter(c));
+                }
+
+                sanitized = String::from(sanitized.trim_end_matches(['.', ' ']));
+                if sanitized.is_empty() {
+                    sanitized = String::from(DEFAULT_REPLACEMENT);
+                }
+            }
+
+            let stem_length = sanitized.find('.').unwrap_or(sanitized.len());
+            let stem = sanitized[..stem_length].trim_end().to_uppercase();
+
+            if RESERVED_WINDOWS_NAMES.contains(&stem.as_str()) {
+                if self.replacements.is_none() {
+                    return Err(SanitizationError::ReservedName(String::from(component)));
+                }
+
+                sanitized.insert_str(stem_length, DEFAULT_REPLACEMENT);
+            }
+        }
+
+        Ok(sanitized)
+    }
+
+    fn is_invalid_character(&self, c: char) -> bool {
+        let is_invalid_on_windows = || "<>:\"\\|?*".contains(c) || c < ' ';
+
+        match self.platform {
+            Platform::Posix => c == '\0',
+            Platform::Windows => is_invalid_on_windows(),
+            Platform::MacOs => c == '\0' || c == ':',
+            Platform::Portable => is_invalid_on_windows() || c.is_control(),
+        }
+    }
+
+    fn forbids_windows_names(&self) -> bool {
+        self.platform == Platform::Windows || self.platform == Platform::Portable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_names() {
+        let sanitizer = Sanitizer::new(Platform::Portable, None).unwrap();
+
+        assert_eq!(
+            Ok(String::from("photos/2024 holiday.jpg")),
+            sanitizer.sanitize("photos/2024 holiday.jpg")
+        );
+        assert_eq!(Ok(String::from("../a")), sanitizer.sanitize("../a"));
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        let windows = Sanitizer::new(Platform::Windows, None).unwrap();
+
+        assert_eq!(
+            Err(SanitizationError::InvalidCharacter(':')),
+            windows.sanitize("12:30.txt")
+        );
+        assert_eq!(
+            Err(SanitizationError::TrailingCharacter('.')),
+            windows.sanitize("notes./a.txt")
+        );
+        assert_eq!(
+            Err(SanitizationError::ReservedName(String::from("con.txt"))),
+            windows.sanitize("con.txt")
+        );
+        assert_eq!(
+            Err(SanitizationError::InvalidCharacter('\u{7f}')),
+            Sanitizer::new(Platform::Portable, None)
+                .unwrap()
+                .sanitize("a\u{7f}")
+        );
+        assert_eq!(
+            Ok(String::from("12:30 .txt")),
+            Sanitizer::new(Platform::Posix, None)
+                .unwrap()
+                .sanitize("12:30 .txt")
+        );
+        assert_eq!(
+            Err(SanitizationError::InvalidCharacter(':')),
+            Sanitizer::new(Platform::MacOs, None)
+                .unwrap()
+                .sanitize("12:30")
+        );
+    }
+
+    #[test]
+    fn rewrites_invalid_names() {
+        let sanitizer = Sanitizer::new(
+            Platform::Windows,
+            Some(Replacements::parse(":=-,?=").unwrap()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(String::from("12-30 what_.txt")),
+            sanitizer.sanitize("12:30 what?*.txt")
+        );
+        assert_eq!(
+            Ok(String::from("notes/_")),
+            sanitizer.sanitize("notes. /. .")
+        );
+        assert_eq!(Ok(String::from("CON_.txt")), sanitizer.sanitize("CON.txt"));
+        assert_eq!(Ok(String::from("LPT0_")), sanitizer.sanitize("LPT0"));
+        assert_eq!(
+            Ok(String::from("conout$_.log")),
+            sanitizer.sanitize("conout$.log")
+        );
+        assert_eq!(Ok(String::from("a/_")), sanitizer.sanitize("a/?"));
+    }
+
+    #[test]
+    fn rejects_invalid_replacements() {
+        let sanitizer = |platform, replacements| {
+            Sanitizer::new(platform, Some(Replacements::parse(replacements).unwrap())).map(|_| ())
+        };
+
+        assert_eq!(
+            Err(SanitizationError::InvalidReplacement(
+                ':',
+                String::from("/")
+            )),
+            sanitizer(Platform::Posix, ":=/")
+        );
+        assert_eq!(
+            Err(SanitizationError::InvalidReplacement(
+                '?',
+                String::from("*")
+            )),
+            sanitizer(Platform::Windows, "?=*")
+        );
+        assert_eq!(
+            Err(SanitizationError::InvalidReplacement(
+                '*',
+                String::from("a:b")
+            )),
+            sanitizer(Platform::MacOs, "*=a:b")
+        );
+        assert_eq!(Ok(()), sanitizer(Platform::Posix, "?=*"));
+    }
+
+    #[test]
+    fn parses_replacements() {
+        assert_eq!(
+            Err(String::from("Invalid replacement: \"ab\"")),
+            Replacements::parse("ab")
+        );
+        assert_eq!(Ok(Replacements::default()), Replacements::parse(""));
+    }
+}
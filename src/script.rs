@@ -0,0 +1,186 @@
+use crate::executor::{Rename, Step};
+use std::path::Path;
+
+/// The commands that scripts use to move files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ScriptFlavor {
+    /// `mv -n`, which does not replace existing files, but has to be checked
+    /// afterwards as it does not fail either
+    Shell,
+    /// `git mv`, which refuses to replace existing files
+    Git,
+}
+
+impl ScriptFlavor {
+    fn move_command(self) -> &'static str {
+        match self {
+            ScriptFlavor::Shell => "move",
+            ScriptFlavor::Git => "git mv --",
+        }
+    }
+
+    /// Definitions that the commands rely on.
+    fn preamble(self) -> &'static str {
+        match self {
+            ScriptFlavor::Shell => {
+                "\nmove() {\n\
+                 \tmv -n -- \"$1\" \"$2\"\n\
+                 \tif [ -e \"$1\" ] || [ -L \"$1\" ]; then\n\
+                 \t\techo \"Unable to move $1 to $2, which already exists\" >&2\n\
+                 \t\texit 1\n\
+                 \tfi\n\
+                 }\n"
+            }
+            ScriptFlavor::Git => "",
+        }
+    }
+}
+
+/// Creates a POSIX shell script that performs the steps in order, relative to the
+/// directory it is run in. Cycles are broken up by moving one file to a temporary name.
+pub(crate) fn create_script(steps: &[Step], flavor: ScriptFlavor) -> String {
+    let mut script = format!("#!/bin/sh\nset -e\n{}", flavor.preamble());
+    let mut temporary_names = 0;
+
+    for step in steps {
+        script.push('\n');
+
+        match step {
+            Step::Move(rename) => {
+                push_directory(&mut script, &rename.target);
+                push_move(&mut script, flavor, &rename.source, &rename.target);
+            }
+            // Cycles only involve existing files, so their directories exist
+            Step::Cycle(renames) => {
+                temporary_names += 1;
+                let first = &renames[0];
+                let temporary = first
+                    .source
+                    .with_file_name(format!(".mvr-{}.tmp", temporary_names));
+
+                push_move(&mut script, flavor, &first.source, &temporary);
+                for Rename { source, target } in renames[1..].iter().rev() {
+                    push_move(&mut script, flavor, source, target);
+                }
+                push_move(&mut script, flavor, &temporary, &first.target);
+            }
+        }
+    }
+
+    script
+}
+
+fn push_directory(script: &mut String, target: &Path) {
+    if let Some(parent) = target.parent().filter(|parent| *parent != Path::new("")) {
+        script.push_str(&format!("mkdir -p -- {}\n", quote(parent)));
+    }
+}
+
+fn push_move(script: &mut String, flavor: ScriptFlavor, source: &Path, target: &Path) {
+    script.push_str(&format!(
+        "{} {} {}\n",
+        flavor.move_command(),
+        quote(source),
+        quote(target)
+    ));
+}
+
+/// Quotes the path for the shell, e.g. `it's` becomes `'it'\''s'`.
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    fn rename(source: &str, target: &str) -> Rename {
+        Rename {
+            source: PathBuf::from(source),
+            target: PathBuf::from(target),
+        }
+    }
+
+    #[test]
+    fn quotes_paths() {
+        assert_eq!("'a b.txt'", quote(Path::new("a b.txt")));
+        assert_eq!("'it'\\''s $HOME'", quote(Path::new("it's $HOME")));
+    }
+
+    #[test]
+    fn creates_shell_scripts() {
+        let steps = vec![
+            Step::Move(rename("a.txt", "new/a.txt")),
+            Step::Cycle(vec![
+                rename("dir/b", "dir/c"),
+                rename("dir/c", "dir/d"),
+                rename("dir/d", "dir/b"),
+            ]),
+        ];
+
+        assert_eq!(
+            "\n\
+             mkdir -p -- 'new'\n\
+             move 'a.txt' 'new/a.txt'\n\
+             \n\
+             move 'dir/b' 'dir/.mvr-1.tmp'\n\
+             move 'dir/d' 'dir/b'\n\
+             move 'dir/c' 'dir/d'\n\
+             move 'dir/.mvr-1.tmp' 'dir/c'\n",
+            create_script(&steps, ScriptFlavor::Shell)
+                .split_once("}\n")
+                .unwrap()
+                .1
+        );
+    }
+
+    #[test]
+    fn shell_scripts_fail_on_existing_targets() {
+        let directory = temp_dir().join(format!("mvr-script-{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+        for name in &["a", "b", "c"] {
+            write(directory.join(name), name).unwrap();
+        }
+        let script = create_script(
+            &[
+                Step::Move(rename("a", "x")),
+                Step::Move(rename("b", "c")),
+                Step::Move(rename("x", "y")),
+            ],
+            ScriptFlavor::Shell,
+        );
+
+        let output = Command::new("sh")
+            .args(["-c", &script])
+            .current_dir(&directory)
+            .output()
+            .unwrap();
+        let contents: Vec<_> = ["x", "b", "c"]
+            .iter()
+            .map(|name| read_to_string(directory.join(name)).unwrap())
+            .collect();
+        let is_stopped = !directory.join("y").exists();
+        remove_dir_all(&directory).unwrap();
+
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(!output.status.success());
+        assert_eq!(
+            "Unable to move b to c, which already exists\n",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(vec!["a", "b", "c"], contents);
+        assert!(is_stopped);
+    }
+
+    #[test]
+    fn creates_git_scripts() {
+        assert_eq!(
+            "#!/bin/sh\nset -e\n\ngit mv -- 'a' 'b'\n",
+            create_script(&[Step::Move(rename("a", "b"))], ScriptFlavor::Git)
+        );
+    }
+}
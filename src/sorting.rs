@@ -0,0 +1,171 @@
+use crate::metadata_filter::FileProperties;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SortKey {
+    Name,
+    ModificationTime,
+    Size,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct SortOrder {
+    pub(crate) key: SortKey,
+    pub(crate) reverse: bool,
+}
+
+impl SortOrder {
+    /// Compares two files by the sort key, falling back to their names if the keys are equal.
+    pub(crate) fn compare(
+        &self,
+        (first_name, first_properties): (&str, &FileProperties),
+        (second_name, second_properties): (&str, &FileProperties),
+    ) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::ModificationTime => first_properties.modified.cmp(&second_properties.modified),
+            SortKey::Size => first_properties.size.cmp(&second_properties.size),
+        }
+        .then_with(|| natural_cmp(first_name, second_name));
+
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Name,
+            reverse: false,
+        }
+    }
+}
+
+/// Compares strings so that embedded numbers are ordered by their value,
+/// e.g. `file2` comes before `file10`.
+pub(crate) fn natural_cmp(first: &str, second: &str) -> Ordering {
+    let mut first_chars = first.chars().peekable();
+    let mut second_chars = second.chars().peekable();
+
+    loop {
+        let ordering = match (first_chars.peek(), second_chars.peek()) {
+            (None, None) => return first.cmp(second),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(first_char), Some(second_char))
+                if first_char.is_ascii_digit() && second_char.is_ascii_digit() =>
+            {
+                compare_numbers(
+                    &take_number(&mut first_chars),
+                    &take_number(&mut second_chars),
+                )
+            }
+            (Some(first_char), Some(second_char)) => {
+                let ordering = first_char.cmp(second_char);
+                first_chars.next();
+                second_chars.next();
+                ordering
+            }
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+
+    while let Some(digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+        number.push(*digit);
+        chars.next();
+    }
+
+    number
+}
+
+fn compare_numbers(first: &str, second: &str) -> Ordering {
+    let first_without_zeros = first.trim_start_matches('0');
+    let second_without_zeros = second.trim_start_matches('0');
+
+    first_without_zeros
+        .len()
+        .cmp(&second_without_zeros.len())
+        .then_with(|| first_without_zeros.cmp(second_without_zeros))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata_filter::FileType;
+    use std::time::{Duration, SystemTime};
+
+    fn properties(size: u64, modified: u64) -> FileProperties {
+        FileProperties {
+            file_type: FileType::File,
+            size,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified)),
+            changed: None,
+            owner: 0,
+            executable: false,
+        }
+    }
+
+    #[test]
+    fn sorts_numbers_by_value() {
+        let mut names = vec!["file10.txt", "file2.txt", "file1.txt", "file02.txt"];
+
+        names.sort_by(|first, second| natural_cmp(first, second));
+
+        assert_eq!(
+            vec!["file1.txt", "file02.txt", "file2.txt", "file10.txt"],
+            names
+        );
+    }
+
+    #[test]
+    fn sorts_text_lexicographically() {
+        let mut names = vec!["b", "a10", "a", "a9b"];
+
+        names.sort_by(|first, second| natural_cmp(first, second));
+
+        assert_eq!(vec!["a", "a9b", "a10", "b"], names);
+    }
+
+    #[test]
+    fn sorts_by_key_then_by_name() {
+        let order = SortOrder {
+            key: SortKey::Size,
+            reverse: false,
+        };
+
+        assert_eq!(
+            Ordering::Greater,
+            order.compare(("a", &properties(20, 0)), ("b", &properties(10, 0)))
+        );
+        assert_eq!(
+            Ordering::Less,
+            order.compare(("a2", &properties(10, 0)), ("a10", &properties(10, 0)))
+        );
+    }
+
+    #[test]
+    fn sorts_in_reverse() {
+        let order = SortOrder {
+            key: SortKey::ModificationTime,
+            reverse: true,
+        };
+
+        assert_eq!(
+            Ordering::Less,
+            order.compare(("a", &properties(0, 20)), ("b", &properties(0, 10)))
+        );
+    }
+}
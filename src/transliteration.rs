@@ -0,0 +1,69 @@
+use deunicode::deunicode_char;
+use unicode_normalization::UnicodeNormalization;
+
+/// Transliterates the input to ASCII, e.g. `Müller – Ærø` becomes `Mueller - AEro`.
+/// German umlauts are expanded (`ü` becomes `ue`), other diacritics are dropped.
+/// Decomposed input, e.g. from macOS, is composed first to be transliterated the same way.
+pub(crate) fn to_ascii(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+
+    for c in input.nfc() {
+        match c {
+            'ä' => output.push_str("ae"),
+            'ö' => output.push_str("oe"),
+            'ü' => output.push_str("ue"),
+            'Ä' => output.push_str("Ae"),
+            'Ö' => output.push_str("Oe"),
+            'Ü' => output.push_str("Ue"),
+            'ß' => output.push_str("ss"),
+            c if c.is_ascii() => output.push(c),
+            c => output.push_str(deunicode_char(c).unwrap_or("")),
+        }
+    }
+
+    output
+}
+
+/// Converts the input to lowercase ASCII words separated by hyphens,
+/// e.g. `Müller – Übersicht (Entwurf)` becomes `mueller-uebersicht-entwurf`.
+pub(crate) fn slugify(input: &str) -> String {
+    to_ascii(input)
+        .to_lowercase()
+        .replace('\'', "")
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transliterates_to_ascii() {
+        assert_eq!("Mueller - Uebersicht", to_ascii("Müller – Übersicht"));
+        assert_eq!("Strasse cafe", to_ascii("Straße café"));
+        assert_eq!("AEro oeuvre fin", to_ascii("Ærø œuvre ﬁn"));
+        assert_eq!("Moskva", to_ascii("Москва"));
+    }
+
+    #[test]
+    fn transliterates_decomposed_input_like_composed_input() {
+        assert_eq!("Mueller cafe", to_ascii("Mu\u{308}ller cafe\u{301}"));
+        assert_eq!(
+            "mueller-uebersicht",
+            slugify("Mu\u{308}ller U\u{308}bersicht")
+        );
+    }
+
+    #[test]
+    fn converts_to_slug() {
+        assert_eq!(
+            "mueller-uebersicht-entwurf",
+            slugify("Müller – Übersicht (Entwurf)")
+        );
+        assert_eq!("dont-stop-me-now", slugify("Don't Stop Me Now!"));
+        assert_eq!("", slugify("--"));
+    }
+}
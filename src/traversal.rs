@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fs::{canonicalize, symlink_metadata};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug)]
+pub(crate) enum TraversalError {
+    OutsideRoot(PathBuf),
+    Unresolvable(PathBuf, io::Error),
+}
+
+impl Display for TraversalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraversalError::OutsideRoot(target) => {
+                write!(f, "The target {:?} is outside of the directory", target)
+            }
+            TraversalError::Unresolvable(target, error) => {
+                write!(f, "Unable to resolve the target {:?}: {}", target, error)
+            }
+        }
+    }
+}
+
+impl Error for TraversalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TraversalError::OutsideRoot(_) => None,
+            TraversalError::Unresolvable(_, error) => Some(error),
+        }
+    }
+}
+
+/// Refuses new names that would move files out of the root directory,
+/// e.g. because they contain `..`, are absolute or lead through a symlinked directory.
+#[derive(Debug)]
+pub(crate) struct TraversalGuard {
+    root: PathBuf,
+}
+
+impl TraversalGuard {
+    pub(crate) fn new(root: &Path) -> io::Result<Self> {
+        Ok(Self {
+            root: canonicalize(root)?,
+        })
+    }
+
+    pub(crate) fn check(&self, new_name: &str) -> Result<(), TraversalError> {
+        let target = self.root.join(new_name);
+        let resolved_target = resolve(&target)
+            .map_err(|error| TraversalError::Unresolvable(target.clone(), error))?;
+
+        match resolved_target {
+            Some(resolved_target)
+                if resolved_target.starts_with(&self.root) && resolved_target != self.root =>
+            {
+                Ok(())
+            }
+            _ => Err(TraversalError::OutsideRoot(target)),
+        }
+    }
+}
+
+/// Normalizes an absolute path the way the kernel would resolve it when renaming:
+/// symlinks in the directories are followed, but the last component is taken as is.
+/// Returns `None` if the path does not end in a file name.
+fn resolve(path: &Path) -> io::Result<Option<PathBuf>> {
+    let file_name = match path.components().next_back() {
+        Some(Component::Normal(file_name)) => file_name,
+        _ => return Ok(None),
+    };
+
+    let mut directory = PathBuf::from("/");
+
+    for component in path.parent().into_iter().flat_map(Path::components) {
+        match component {
+            Component::Prefix(_) | Component::RootDir => directory = PathBuf::from("/"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                directory.pop();
+            }
+            Component::Normal(name) => {
+                directory.push(name);
+
+                let is_symlink = symlink_metadata(&directory)
+                    .map(|metadata| metadata.file_type().is_symlink())
+                    .unwrap_or(false);
+
+                if is_symlink {
+                    directory = canonicalize(&directory)?;
+                }
+            }
+        }
+    }
+
+    Ok(Some(directory.join(file_name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn accepts_targets_inside_the_root() {
+        let guard = TraversalGuard {
+            root: PathBuf::from("/nonexistent-root"),
+        };
+
+        assert!(guard.check("a.txt").is_ok());
+        assert!(guard.check("new/../dir/./a.txt").is_ok());
+    }
+
+    #[test]
+    fn refuses_targets_outside_the_root() {
+        let guard = TraversalGuard {
+            root: PathBuf::from("/nonexistent-root"),
+        };
+
+        for new_name in &["../a.txt", "dir/../../a.txt", "/etc/a.txt", "..", "."] {
+            assert!(matches!(
+                guard.check(new_name),
+                Err(TraversalError::OutsideRoot(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn refuses_targets_behind_symlinked_directories() {
+        let directory = temp_dir().join(format!("mvr-traversal-{}", std::process::id()));
+        let root = directory.join("root");
+        create_dir_all(&root).unwrap();
+        create_dir_all(directory.join("outside")).unwrap();
+        symlink("../outside", root.join("link")).unwrap();
+        symlink(".", root.join("self")).unwrap();
+
+        let guard = TraversalGuard::new(&root).unwrap();
+        let link_result = guard.check("link/a.txt");
+        let self_result = guard.check("self/self/a.txt");
+        let replaced_link_result = guard.check("link");
+
+        remove_dir_all(&directory).unwrap();
+
+        assert!(matches!(link_result, Err(TraversalError::OutsideRoot(_))));
+        assert!(self_result.is_ok());
+        assert!(replaced_link_result.is_ok());
+    }
+}